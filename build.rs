@@ -1,16 +1,19 @@
 use std::env;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
-fn read_toml_trnsys_metadata() -> toml::Value {
+fn read_cargo_toml() -> toml::Value {
     let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
     let cargo_toml_path = Path::new(&manifest_dir).join("Cargo.toml");
 
     let cargo_toml_content = fs::read_to_string(cargo_toml_path).unwrap();
 
-    cargo_toml_content
-        .parse::<toml::Value>()
-        .unwrap()
+    cargo_toml_content.parse::<toml::Value>().unwrap()
+}
+
+fn read_toml_trnsys_metadata(cargo_toml: &toml::Value) -> toml::Value {
+    cargo_toml
         .get("package")
         .and_then(|pkg| pkg.get("metadata"))
         .and_then(|meta| meta.get("trnsys"))
@@ -18,13 +21,32 @@ fn read_toml_trnsys_metadata() -> toml::Value {
         .to_owned()
 }
 
-fn write_entrance_code(type_number: &str) {
+/// The name of the DLL, without the extension.
+fn library_name(cargo_toml: &toml::Value) -> String {
+    cargo_toml
+        .get("lib")
+        .and_then(|lib| lib.get("name"))
+        .and_then(toml::Value::as_str)
+        .map(str::to_string)
+        .unwrap_or_else(|| env::var("CARGO_PKG_NAME").unwrap().replace('-', "_"))
+}
+
+/// An optional string of `[package.metadata.trnsys]`.
+fn optional_str<'a>(metadata: &'a toml::Value, key: &str) -> Option<&'a str> {
+    metadata.get(key).map(|value| {
+        value
+            .as_str()
+            .unwrap_or_else(|| panic!("`{key}` in `Cargo.toml` must be a string, found {value}"))
+    })
+}
+
+fn write_entrance_code(type_number: &str, trnsys_version: i64, mangling: FortranMangling) {
     // Get Output Dir
     let out_dir = env::var("OUT_DIR").unwrap();
     let dest_path = Path::new(&out_dir).join("generated_entrance.rs");
 
     // Dynamic generate the type entrance
-    let function_code = format!(
+    let mut function_code = format!(
         r#"
         #[allow(non_snake_case)]
         #[unsafe(no_mangle)]
@@ -32,46 +54,117 @@ fn write_entrance_code(type_number: &str) {
             entrance();
         }}
         const TYPE_NUMBER: u32 = {};
+        const TRNSYS_VERSION: i32 = {};
         "#,
-        type_number, type_number
+        type_number, type_number, trnsys_version
     );
 
+    // A gfortran kernel looks the Type up under its own symbol
+    if let FortranMangling::Gfortran = mangling {
+        function_code.push_str(&format!(
+            r#"
+        #[allow(non_snake_case)]
+        #[unsafe(export_name = "{}")]
+        pub extern "C" fn TYPE{}_gfortran() {{
+            entrance();
+        }}
+        "#,
+            mangling.symbol(&format!("TYPE{type_number}")),
+            type_number
+        ));
+    }
+
     // Write code to the file
     fs::write(&dest_path, function_code).unwrap();
 }
 fn main() {
     // Tell Cargo that if the given file changes, to rerun this build script.
-    watch_trndll64();
+    println!("cargo::rerun-if-changed=Cargo.toml");
+    println!("cargo::rerun-if-changed={KERNEL_DECLARATIONS}");
+    println!("cargo::rustc-check-cfg=cfg(mock_kernel)");
 
-    // Read type number
-    let metadata = read_toml_trnsys_metadata();
+    let cargo_toml = read_cargo_toml();
+    let metadata = read_toml_trnsys_metadata(&cargo_toml);
 
+    // The kernel functions, under the symbols of the Fortran compiler of the kernel
+    let mangling = FortranMangling::from_metadata(&metadata);
+    let declarations = fs::read_to_string(KERNEL_DECLARATIONS).unwrap();
+    write_kernel_symbols(&declarations, mangling);
+    if uses_mock_kernel() {
+        println!("cargo:rustc-cfg=mock_kernel");
+    } else {
+        link_trndll64(&declarations, mangling);
+    }
+
+    // Read type number
     if let Some(type_number) = metadata.get("type_number").unwrap().as_integer() {
         println!("Found type number: {}", type_number);
 
-        write_entrance_code(&type_number.to_string());
+        // The version the Type signs with, TRNSYS 18 if not given
+        let trnsys_version = match metadata.get("trnsys_version") {
+            None => 18,
+            Some(version) => match version.as_integer() {
+                Some(version @ (17 | 18)) => version,
+                _ => panic!("`trnsys_version` in `Cargo.toml` must be 17 or 18, found {version}"),
+            },
+        };
+        println!("Signing for TRNSYS {}", trnsys_version);
+
+        write_entrance_code(&type_number.to_string(), trnsys_version, mangling);
+        write_windows_resources(&cargo_toml, &metadata, type_number, trnsys_version);
     } else {
         panic!("Cannot find type number in `Cargo.toml`. Please add it before building.")
     }
+}
+
+/// Adds the version information and the icon of the DLL, from the package and
+/// `[package.metadata.trnsys]`. Skipped if the target is not Windows, or if the resource compiler
+/// (`windres` or `rc.exe`) is not found.
+fn write_windows_resources(
+    cargo_toml: &toml::Value,
+    metadata: &toml::Value,
+    type_number: i64,
+    trnsys_version: i64,
+) {
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("windows") {
+        return;
+    }
+
+    let package = env::var("CARGO_PKG_NAME").unwrap();
+    let description = env::var("CARGO_PKG_DESCRIPTION").unwrap_or_default();
+    let authors = env::var("CARGO_PKG_AUTHORS")
+        .unwrap_or_default()
+        .replace(':', ", ");
+    let library = library_name(cargo_toml);
 
-    // Add Windows resources (icon, version info, etc.)
     let mut res = winres::WindowsResource::new();
+    if description.is_empty() {
+        res.set("FileDescription", &format!("TRNSYS Type {type_number}"));
+    } else {
+        res.set("FileDescription", &description);
+    }
     res.set(
-        "FileDescription",
-        "Type 395 HAMT Model",
+        "ProductName",
+        optional_str(metadata, "product_name").unwrap_or(&package),
     );
-    res.set("ProductName", "TRNSYS HAMT");
-    res.set("OriginalFilename", "trnsys_hamt_rs.dll");
-
-    let version = std::env::var("CARGO_PKG_VERSION").unwrap();
-
-    let authors = std::env::var("CARGO_PKG_AUTHORS").unwrap();
-
+    res.set("InternalName", &library);
+    res.set("OriginalFilename", &format!("{library}.dll"));
     res.set(
-        "LegalCopyright",
-        &format!("Copyright © 2026 {authors}. All rights reserved.W"),
+        "Comments",
+        &format!("Type {type_number} for TRNSYS {trnsys_version}"),
     );
+    if !authors.is_empty() {
+        res.set("CompanyName", &authors);
+        res.set("LegalCopyright", &format!("Copyright © {authors}"));
+    }
+
+    if let Some(icon) = optional_str(metadata, "icon") {
+        let icon = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join(icon);
+        println!("cargo::rerun-if-changed={}", icon.display());
+        res.set_icon(&icon.to_string_lossy());
+    }
 
+    let version = std::env::var("CARGO_PKG_VERSION").unwrap();
     let version_parts: Vec<&str> = version.split('.').collect();
     if version_parts.len() >= 3 {
         res.set_version_info(
@@ -84,7 +177,9 @@ fn main() {
         );
     }
 
-    res.compile().unwrap();
+    if let Err(e) = res.compile() {
+        println!("cargo::warning=Skipping the Windows resources of the DLL: {e}");
+    }
 }
 
 fn parse_version(major: &str, minor: &str, patch: &str, build: &str) -> u64 {
@@ -96,9 +191,196 @@ fn parse_version(major: &str, minor: &str, patch: &str, build: &str) -> u64 {
     (v1 << 48) | (v2 << 32) | (v3 << 16) | v4
 }
 
-/// Watch the TRNDll64.lib file and rebuild if it changes
-fn watch_trndll64() {
+/// Whether the kernel functions are provided by `src/trnsys/mock_kernel.rs` instead of
+/// `TRNDll64.dll`: with the `mock-kernel` feature, and on targets other than Windows, where
+/// TRNSYS doesn't run, so that the Type can be built and tested on any host.
+fn uses_mock_kernel() -> bool {
+    env::var_os("CARGO_FEATURE_MOCK_KERNEL").is_some()
+        || env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("windows")
+}
+
+/// The declarations of the kernel functions the Type imports.
+const KERNEL_DECLARATIONS: &str = "src/trnsys/ext_c.rs";
+
+/// How the Fortran compiler of the kernel names its symbols, `fortran_mangling` in
+/// `[package.metadata.trnsys]`. The functions are declared in `ext_c.rs` under their Intel Fortran
+/// names, the names of the official kernel.
+#[derive(Debug, Clone, Copy)]
+enum FortranMangling {
+    /// `TRNSYSFUNCTIONS_mp_GETINPUTVALUE` for module procedures, `FOUNDBADINPUT` for subroutines.
+    Intel,
+    /// `__trnsysfunctions_MOD_getinputvalue` for module procedures, `foundbadinput_` for
+    /// subroutines.
+    Gfortran,
+}
+
+impl FortranMangling {
+    fn from_metadata(metadata: &toml::Value) -> Self {
+        match optional_str(metadata, "fortran_mangling") {
+            None | Some("intel") => FortranMangling::Intel,
+            Some("gfortran") => FortranMangling::Gfortran,
+            Some(other) => panic!(
+                "`fortran_mangling` in `Cargo.toml` must be \"intel\" or \"gfortran\", found \"{other}\""
+            ),
+        }
+    }
+
+    /// The symbol of the kernel function declared as `name` in `ext_c.rs`.
+    fn symbol(self, name: &str) -> String {
+        match self {
+            FortranMangling::Intel => name.to_string(),
+            FortranMangling::Gfortran => match name.split_once("_mp_") {
+                Some((module, procedure)) => format!(
+                    "__{}_MOD_{}",
+                    module.to_lowercase(),
+                    procedure.to_lowercase()
+                ),
+                None => format!("{}_", name.to_lowercase()),
+            },
+        }
+    }
+}
+
+/// Generates `kernel_symbol!`, which gives the symbol of a kernel function declared in `ext_c.rs`,
+/// whether it is imported or looked up at runtime.
+fn write_kernel_symbols(declarations: &str, mangling: FortranMangling) {
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let dest_path = Path::new(&out_dir).join("kernel_symbols.rs");
+
+    let mut code = String::from("macro_rules! kernel_symbol {\n");
+    for name in kernel_symbols(declarations)
+        .into_iter()
+        .chain(runtime_kernel_symbols(declarations))
+    {
+        code.push_str(&format!(
+            "    ({name}) => {{ \"{}\" }};\n",
+            mangling.symbol(name)
+        ));
+    }
+    code.push_str("}\n");
+
+    fs::write(&dest_path, code).unwrap();
+}
+
+/// Links the kernel: `lib/TRNDll64.lib` if it is provided, otherwise an import library generated
+/// from the declarations in `src/trnsys/ext_c.rs`, so that no TRNSYS installation is needed to build.
+fn link_trndll64(declarations: &str, mangling: FortranMangling) {
     println!("cargo::rerun-if-changed=lib/TRNDll64.lib");
-    println!("cargo:rustc-link-search=native=lib");
+
+    if Path::new("lib/TRNDll64.lib").exists() {
+        println!("cargo:rustc-link-search=native=lib");
+    } else {
+        let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+        let def_file = out_dir.join("TRNDll64.def");
+        fs::write(&def_file, def_file_content(declarations, mangling)).unwrap();
+
+        match generate_import_library(&def_file, &out_dir) {
+            Ok(tool) => {
+                println!("Generated the import library of TRNDll64.dll with {tool}");
+                println!("cargo:rustc-link-search=native={}", out_dir.display());
+            }
+            Err(e) => panic!(
+                "Cannot generate the import library of TRNDll64.dll ({e}), \
+                copy TRNDll64.lib from the TRNSYS installation into `lib/`"
+            ),
+        }
+    }
     println!("cargo:rustc-link-lib=static=TRNDll64");
 }
+
+/// The functions declared in the `kernel_functions!` block of `ext_c.rs`.
+fn kernel_symbols(declarations: &str) -> Vec<&str> {
+    declarations
+        .lines()
+        .skip_while(|line| !line.starts_with("kernel_functions! {"))
+        .take_while(|line| !line.starts_with('}'))
+        .filter_map(|line| line.trim_start().strip_prefix("pub fn "))
+        .filter_map(|line| line.split_once('(').map(|(name, _)| name.trim()))
+        .collect()
+}
+
+/// The functions declared as types in `ext_c.rs`, which are looked up at runtime.
+fn runtime_kernel_symbols(declarations: &str) -> Vec<&str> {
+    declarations
+        .lines()
+        .filter(|line| line.contains("= unsafe extern \"C\" fn"))
+        .filter_map(|line| line.strip_prefix("pub type "))
+        .filter_map(|line| line.split_once('=').map(|(name, _)| name.trim()))
+        .collect()
+}
+
+/// A module-definition file listing the exports of `TRNDll64.dll` the Type imports.
+fn def_file_content(declarations: &str, mangling: FortranMangling) -> String {
+    let mut def = String::from("LIBRARY TRNDll64.dll\nEXPORTS\n");
+    for name in kernel_symbols(declarations) {
+        def.push_str("    ");
+        def.push_str(&mangling.symbol(name));
+        def.push('\n');
+    }
+    def
+}
+
+/// Generates the import library from the module-definition file with the first tool found:
+/// `lib.exe` or `llvm-lib` for MSVC targets, `dlltool` for GNU targets, `llvm-dlltool` for both.
+/// Returns the tool used.
+fn generate_import_library(def_file: &Path, out_dir: &Path) -> Result<String, String> {
+    let target = env::var("TARGET").unwrap();
+    let def = def_file.to_str().unwrap();
+    let msvc = env::var("CARGO_CFG_TARGET_ENV").as_deref() == Ok("msvc");
+    let library_name = if msvc {
+        "TRNDll64.lib"
+    } else {
+        "libTRNDll64.a"
+    };
+    let library = out_dir.join(library_name);
+    let library = library.to_str().unwrap();
+
+    let dlltool_args = [
+        "-m",
+        "i386:x86-64",
+        "-D",
+        "TRNDll64.dll",
+        "-d",
+        def,
+        "-l",
+        library,
+    ];
+    let lib_args = [
+        "/NOLOGO".to_string(),
+        "/MACHINE:X64".to_string(),
+        format!("/DEF:{def}"),
+        format!("/OUT:{library}"),
+    ];
+    let mut candidates = vec![];
+    if msvc {
+        if let Some(mut lib) = cc::windows_registry::find(&target, "lib.exe") {
+            lib.args(&lib_args);
+            candidates.push(("lib.exe", lib));
+        }
+        let mut llvm_lib = Command::new("llvm-lib");
+        llvm_lib.args(&lib_args);
+        candidates.push(("llvm-lib", llvm_lib));
+    } else {
+        for tool in ["x86_64-w64-mingw32-dlltool", "dlltool"] {
+            let mut dlltool = Command::new(tool);
+            dlltool.args(dlltool_args);
+            candidates.push((tool, dlltool));
+        }
+    }
+    let mut llvm_dlltool = Command::new("llvm-dlltool");
+    llvm_dlltool.args(dlltool_args);
+    candidates.push(("llvm-dlltool", llvm_dlltool));
+
+    let mut tried = vec![];
+    for (tool, mut command) in candidates {
+        match command.output() {
+            Ok(output) if output.status.success() => return Ok(tool.to_string()),
+            Ok(output) => tried.push(format!(
+                "{tool} failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+            Err(e) => tried.push(format!("{tool}: {e}")),
+        }
+    }
+    Err(tried.join("; "))
+}
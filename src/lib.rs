@@ -1,124 +1,539 @@
-use crate::logging::init_tracing;
+use crate::session::{enter_unit, finish_unit};
 use crate::trnsys::error::{TrnSysError, TrnSysErrorHandler};
+use crate::trnsys::index::CallScope;
+use crate::trnsys::iteration_mode::IterationMode;
+use crate::trnsys::kernel_version::{kernel, TrnSysVersion};
+use crate::trnsys::param::{ParameterDiff, TrnSysValue};
 use crate::trnsys_type::TrnSysType;
-use anyhow::{Context, Error, Result};
-use std::collections::HashMap;
+use anyhow::Result;
 use std::ops::DerefMut;
-use std::sync::{Arc, LazyLock, RwLock};
-use tracing::{debug, error, info};
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use tracing::{debug, error, warn};
 use trnsys::*;
 
+// `kernel_symbol!`, the symbols of the kernel functions for the `fortran_mangling` of `Cargo.toml`
+include!(concat!(env!("OUT_DIR"), "/kernel_symbols.rs"));
+
+mod session;
 mod trnsys;
 mod trnsys_type;
 
 include!(concat!(env!("OUT_DIR"), "/generated_entrance.rs"));
 
-static TRNSYS_STATE_DICT: LazyLock<RwLock<HashMap<i32, Arc<RwLock<TrnSysState>>>>> =
-    LazyLock::new(|| RwLock::new(HashMap::new()));
-
-static TRNSYS_TYPE_INSTANCE: LazyLock<Arc<TrnSysType>> = LazyLock::new(|| {
-    // initialize the logging only once
-    init_tracing(None);
-    Arc::new(TrnSysType::new())
-});
-
-fn get_current_state() -> Arc<RwLock<TrnSysState>> {
-    let mut dict = (&TRNSYS_STATE_DICT).write().unwrap();
-    let unit = get_current_unit();
-
-    dict.entry(unit)
-        .or_insert(Arc::new(RwLock::new(TrnSysState::new())))
-        .clone()
-}
-
 fn entrance() {
     // Disable FPU exceptions for Rust's SIMD Optimization
     // Its padding can cause FPU Hardware Interrupts,
     // which is captured by TRNSYS
     let _guard = FpuGuard::new();
-    let state_lock = get_current_state();
-    let mut state = state_lock.write().unwrap();
+    let ctx = CallContext::from_kernel();
+    // the session initializes the logging, before the call span is opened
+    let mut entry = match enter_unit(&ctx) {
+        Ok(entry) => entry,
+        Err(e) => {
+            // without an entry there is no session to log to, the kernel is told directly
+            log_message(
+                Severity::Fatal,
+                -1,
+                ctx.unit,
+                ctx.type_number,
+                &e.to_string(),
+            );
+            return;
+        }
+    };
+    let unit = entry.deref_mut();
+    let _span = ctx.span().entered();
+    // the kernel wrappers check indices and phases against this scope
+    let scope = CallScope::enter(ctx.phase, unit.state.declared_counts());
     // create type instance
-    match main(state.deref_mut()) {
+    match main(&unit.type_instance, &ctx, &scope, &mut unit.state) {
         Ok(_) => {}
         Err(e) => {
-            e.handle_in_trnsys(state.deref_mut());
-            error!("{:?}", e);
+            if e.handle_in_trnsys(&ctx, &unit.state) {
+                // already in the list file, only written to the log files
+                error!(trnsys.reported = true, "{:?}", e);
+            } else {
+                error!("{:?}", e);
+            }
         }
     }
+    // the entry is released first, so that the end of the session can free it
+    drop(entry);
+    if ctx.phase == CallPhase::LastCall {
+        finish_unit(&ctx);
+    }
 }
 
-fn main(mut state: &mut TrnSysState) -> Result<()> {
-    let type_instance = TRNSYS_TYPE_INSTANCE.clone();
+fn main(
+    type_instance: &TrnSysType,
+    ctx: &CallContext,
+    scope: &CallScope,
+    state: &mut TrnSysState,
+) -> Result<()> {
+    match ctx.phase {
+        CallPhase::VersionSigning => {
+            let kernel = kernel();
+            state.type_version = kernel.signing_version();
+            if state.type_version < TrnSysVersion::of_type() {
+                warn!(
+                    "The Type is written for TRNSYS {}, but runs in TRNSYS {}, signing with {}",
+                    TrnSysVersion::of_type().number(),
+                    kernel.version.number(),
+                    state.type_version.number()
+                );
+            }
+            set_type_version(state.type_version.number())?;
+            return Ok(());
+        }
+        CallPhase::FirstCall => {
+            // Tell the TRNSYS Engine How This Type Works
+            state.num_inputs = get_number_of_inputs();
+            state.num_params = get_number_of_parameters();
+            state.num_outputs = get_number_of_outputs();
+            state.num_derivatives = get_number_of_derivatives();
+            scope.declare_counts(state.declared_counts());
+
+            // the sizing phase computes the counts from the parameters and checks the deck
+            let params = (0..state.num_params.max(0) as usize)
+                .map(|i| get_parameter_value(ParamIndex(i)).map(TrnSysValue::from))
+                .collect::<Result<Vec<_>, _>>()?;
+            state.sizes = type_instance.dimensions().size(state, &params)?;
+            state.params = params;
 
-    if is_version_signing_time() {
-        set_type_version(state.trnsys_standard_version);
-        return Ok(());
-    } else if is_first_call_of_simulation() {
-        // Tell the TRNSYS Engine How This Type Works
-        state.num_inputs = get_number_of_inputs();
-        state.num_params = get_number_of_parameters();
-        state.num_outputs = get_number_of_outputs();
-        state.num_derivatives = get_number_of_derivatives();
+            type_instance.first_call_of_simulation(ctx, state)?;
 
-        type_instance.first_call_of_simulation(&mut state)?;
+            debug!("Number of Inputs: {}", state.num_inputs);
+            debug!("Number of Parameters: {}", state.num_params);
+            debug!("Number of Outputs: {}", state.num_outputs);
+            debug!("Number of Derivatives: {}", state.num_derivatives);
 
-        debug!("Number of Inputs: {}", state.num_inputs);
-        debug!("Number of Parameters: {}", state.num_params);
-        debug!("Number of Outputs: {}", state.num_outputs);
-        debug!("Number of Derivatives: {}", state.num_derivatives);
+            scope.declare_counts(state.declared_counts());
+            state.allocate_buffers();
 
-        set_number_of_parameters(state.num_params);
-        set_number_of_inputs(state.num_inputs);
-        set_number_of_derivatives(state.num_derivatives);
-        set_number_of_outputs(state.num_outputs);
-        set_iteration_mode(state.iteration_mode.into());
-        return Ok(());
+            set_number_of_parameters(state.num_params)?;
+            set_number_of_inputs(state.num_inputs)?;
+            set_number_of_derivatives(state.num_derivatives)?;
+            set_number_of_outputs(state.num_outputs)?;
+            set_number_stored_variables(state.num_static_storage, state.num_dynamic_storage)?;
+            for (i, unit) in state.input_units.iter().enumerate() {
+                set_input_units(InputIndex(i), *unit)?;
+            }
+            for (i, unit) in state.output_units.iter().enumerate() {
+                set_output_units(OutputIndex(i), *unit)?;
+            }
+            set_iteration_mode(state.iteration_mode.into())?;
+            return Ok(());
+        }
+        _ => {}
+    }
+    // the parameters only change at the start and when the kernel asks to reread them
+    match ctx.phase {
+        CallPhase::StartTime => state.read_parameter_values()?,
+        CallPhase::RereadParameters => {
+            state.previous_params.copy_from_slice(&state.params);
+            state.read_parameter_values()?;
+            if let Some(diff) = ParameterDiff::between(&state.previous_params, &state.params) {
+                debug!("Parameters changed: {:?}", diff.changed);
+                type_instance.validate_parameters(ctx, state)?;
+                type_instance.on_parameters_changed(ctx, state, &diff)?;
+            }
+        }
+        _ => {}
     }
-    state.read_input_values();
-    state.read_parameter_values();
+    state.read_input_values()?;
+    state.input_check.check(ctx, &state.inputs)?;
 
     // read_storage(&mut state);
 
-    if is_last_call_of_simulation() {
-        type_instance.simulation_ends(&mut state)?;
-        return Ok(());
+    match ctx.phase {
+        CallPhase::LastCall => {
+            state.convergence.log_summary(ctx.unit);
+            state.output_cache.log_summary(ctx.unit);
+            type_instance.simulation_ends(ctx, state)?;
+        }
+        CallPhase::EndOfTimestep => {
+            state.convergence.end_of_timestep(ctx);
+            type_instance.end_of_timestep(ctx, state)?;
+        }
+        CallPhase::StartTime => {
+            // validate parameters
+            type_instance.validate_parameters(ctx, state)?;
+            state.convergence.tolerance = get_convergence_tolerance();
+            // initialize outputs
+            state.default_output_values = type_instance.get_default_output_values(ctx, state)?;
+            for (i, val) in state.default_output_values.iter().enumerate() {
+                set_output_value(OutputIndex(i), val.value)?;
+            }
+            state.output_guard.reset(&state.default_output_values);
+
+            type_instance.simulation_starts(ctx, state)?;
+        }
+        _ => {
+            compute_outputs(type_instance, ctx, state)?;
+            // set output
+            for (i, val) in state.outputs.iter().enumerate() {
+                set_output_value(OutputIndex(i), val.value)?;
+            }
+        }
     }
 
-    if is_end_of_timestep() {
-        type_instance.end_of_timestep(&mut state)?;
-        return Ok(());
+    Ok(())
+}
+
+/// Computes the outputs of an iterative call into `state.outputs`, from the inputs and
+/// parameters already read. It doesn't call the kernel and doesn't allocate.
+fn compute_outputs(
+    type_instance: &TrnSysType,
+    ctx: &CallContext,
+    state: &mut TrnSysState,
+) -> Result<(), TrnSysError> {
+    if state.iteration_mode == IterationMode::StaticMode {
+        if let Some(cached_outputs) = state.output_cache.lookup(&state.inputs, &state.params) {
+            debug!("Inputs unchanged, emitting cached outputs");
+            state.outputs.copy_from_slice(cached_outputs);
+            state.convergence.record_iteration(ctx, &state.outputs);
+            return Ok(());
+        }
     }
+    // Perform All the Calculations Here, the buffer is moved out of the state for the call and
+    // moved back before an error or a panic of `iterate` leaves
+    let mut outputs = std::mem::take(&mut state.outputs);
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        type_instance.iterate(ctx, state, &mut outputs)
+    }));
+    state.outputs = outputs;
+    result.unwrap_or_else(|panic| resume_unwind(panic))?;
+    state
+        .output_guard
+        .check(ctx, &state.inputs, &state.params, &mut state.outputs)?;
+    state.convergence.record_iteration(ctx, &state.outputs);
+    state
+        .output_cache
+        .store(&state.inputs, &state.params, &state.outputs);
+    Ok(())
+}
 
-    if is_start_time() {
-        // validate parameters
-        type_instance.validate_parameters(&mut state)?;
-        // initialize outputs
-        type_instance
-            .get_default_output_values(&mut state)?
-            .iter()
-            .enumerate()
-            .for_each(|(i, val)| {
-                // attention: TRNSYS/Fortran is 1-indexed
-                set_output_value(i as i32, val.value);
-            });
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trnsys::output_cache::InputMatch;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
 
-        type_instance.simulation_starts(&mut state)?;
-        return Ok(());
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
     }
 
-    if is_reread_parameters() {
-        state.read_parameter_values();
-        state.read_input_values();
-        // read_storage(&mut state);
+    /// Counts the allocations of the current thread, so that the other test threads don't count.
+    struct CountingAllocator;
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            System.realloc(ptr, layout, new_size)
+        }
     }
-    // Perform All the Calculations Here
-    let simulation_outputs = type_instance.iterate(&mut state)?;
-    // set output
-    simulation_outputs.iter().enumerate().for_each(|(i, val)| {
-        // attention: TRNSYS/Fortran is 1-indexed
-        set_output_value(i as i32, val.value);
-    });
 
-    Ok(())
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn allocations() -> usize {
+        ALLOCATIONS.with(Cell::get)
+    }
+
+    #[test]
+    fn iterations_do_not_allocate() {
+        let type_instance = TrnSysType::new();
+        let mut state = TrnSysState::new();
+        state.num_inputs = 1;
+        state.num_params = 1;
+        state.num_outputs = 1;
+        state.allocate_buffers();
+        state.params[0] = TrnSysValue::new(3.);
+        state.iteration_mode = IterationMode::StaticMode;
+        state.output_cache.enable(InputMatch::Exact);
+
+        let iterate = |state: &mut TrnSysState, time: f64, iteration: i32, input: f64| {
+            let ctx = CallContext::new(
+                CallPhase::Iteration,
+                time,
+                1.,
+                iteration,
+                1,
+                TYPE_NUMBER as i32,
+            );
+            state.inputs[0] = TrnSysValue::new(input);
+            compute_outputs(&type_instance, &ctx, state).unwrap();
+            ctx
+        };
+
+        // the first time step sizes the convergence history and the output cache
+        iterate(&mut state, 1., 1, 1.);
+        let ctx = iterate(&mut state, 1., 2, 2.);
+        state.convergence.end_of_timestep(&ctx);
+
+        let before = allocations();
+        for step in 2..100 {
+            let time = step as f64;
+            iterate(&mut state, time, 1, time);
+            // a cache hit
+            iterate(&mut state, time, 2, time);
+            let ctx = iterate(&mut state, time, 3, time + 0.5);
+            state.convergence.end_of_timestep(&ctx);
+        }
+        assert_eq!(allocations() - before, 0);
+        assert_eq!(state.outputs[0].value, 298.5);
+    }
+
+    #[test]
+    fn iterate_gives_the_outputs_back_on_errors_and_panics() {
+        let type_instance = TrnSysType::new();
+        let mut state = TrnSysState::new();
+        state.num_inputs = 1;
+        state.num_params = 1;
+        state.num_outputs = 1;
+        state.allocate_buffers();
+        let ctx = CallContext::new(CallPhase::Iteration, 1., 1., 1, 1, TYPE_NUMBER as i32);
+
+        // the template's parameter must be an integer
+        state.params[0] = TrnSysValue::new(2.5);
+        assert!(compute_outputs(&type_instance, &ctx, &mut state).is_err());
+        assert_eq!(state.outputs.len(), 1);
+
+        // the template reads an input the state doesn't have
+        state.inputs.clear();
+        let panic = catch_unwind(AssertUnwindSafe(|| {
+            compute_outputs(&type_instance, &ctx, &mut state)
+        }));
+        assert!(panic.is_err());
+        assert_eq!(state.outputs.len(), 1);
+    }
+
+    /// Runs `main` for a call in `phase` against the mock kernel, without the span of `entrance()`.
+    #[cfg(mock_kernel)]
+    fn call_main(type_instance: &TrnSysType, state: &mut TrnSysState, phase: CallPhase) {
+        use crate::trnsys::mock_kernel::with_mock_kernel;
+
+        with_mock_kernel(|kernel| kernel.phase = phase);
+        let ctx = CallContext::from_kernel();
+        let scope = CallScope::enter(ctx.phase, state.declared_counts());
+        main(type_instance, &ctx, &scope, state).unwrap();
+    }
+
+    #[test]
+    #[cfg(mock_kernel)]
+    fn kernel_calls_do_not_allocate() {
+        use crate::trnsys::mock_kernel::{with_mock_kernel, MockKernel};
+
+        let _simulation = mock_simulation("trnsys_mock_kernel_allocations", "");
+        let type_instance = TrnSysType::new();
+        let mut state = TrnSysState::new();
+        for phase in [
+            CallPhase::VersionSigning,
+            CallPhase::FirstCall,
+            CallPhase::StartTime,
+            CallPhase::Iteration,
+            CallPhase::RereadParameters,
+            CallPhase::EndOfTimestep,
+        ] {
+            call_main(&type_instance, &mut state, phase);
+        }
+        with_mock_kernel(MockKernel::advance_timestep);
+
+        let before = allocations();
+        for step in 2..50 {
+            with_mock_kernel(|kernel| kernel.inputs[0] = step as f64);
+            call_main(&type_instance, &mut state, CallPhase::Iteration);
+            // the kernel sends it at the start of every time step with several units
+            call_main(&type_instance, &mut state, CallPhase::RereadParameters);
+            call_main(&type_instance, &mut state, CallPhase::EndOfTimestep);
+            with_mock_kernel(MockKernel::advance_timestep);
+        }
+        assert_eq!(allocations() - before, 0);
+        assert_eq!(with_mock_kernel(|kernel| kernel.outputs[0]), 3. * 49.);
+    }
+
+    /// The session is shared by the process, so the simulations of the tests run one at a time.
+    #[cfg(mock_kernel)]
+    static SIMULATION: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    /// Sets up the mock kernel of the test thread for a simulation with a deck in `deck_dir`, and
+    /// the logging settings `log_config` in its `trnsys_log.toml`.
+    ///
+    /// The first simulation installs the subscriber of the test process, so all of them use the
+    /// same logging config, without debug events which would allocate in the other tests. The
+    /// simulation has the list file limiter to itself, with the limits of the config.
+    #[cfg(mock_kernel)]
+    fn mock_simulation(
+        deck_dir: &str,
+        log_config: &str,
+    ) -> (
+        std::sync::MutexGuard<'static, ()>,
+        std::sync::MutexGuard<'static, ()>,
+    ) {
+        use crate::trnsys::list_file_limiter::reset_list_file_limiter;
+        use crate::trnsys::log_config::{ListFileLimits, LOG_CONFIG_FILE};
+        use crate::trnsys::mock_kernel::with_mock_kernel;
+
+        let guard = SIMULATION.lock().unwrap_or_else(|e| e.into_inner());
+        let limiter = reset_list_file_limiter(ListFileLimits::default());
+        let deck_dir = std::env::temp_dir().join(deck_dir);
+        std::fs::create_dir_all(&deck_dir).unwrap();
+        // without the console, which the test harness doesn't capture
+        let log_config = format!("filter = \"info\"\nconsole = false\n{}", log_config);
+        std::fs::write(deck_dir.join(LOG_CONFIG_FILE), log_config).unwrap();
+        with_mock_kernel(|kernel| {
+            kernel.input_file_dir = deck_dir.to_string_lossy().into_owned();
+            kernel.deck_file = deck_dir.join("mock.dck").to_string_lossy().into_owned();
+            kernel.params = vec![3.];
+            kernel.inputs = vec![1.];
+            kernel.num_outputs = 1;
+        });
+        (guard, limiter)
+    }
+
+    #[test]
+    #[cfg(mock_kernel)]
+    fn simulation_runs_against_the_mock_kernel() {
+        use crate::trnsys::mock_kernel::{call_type, with_mock_kernel, MockKernel};
+
+        let _simulation = mock_simulation("trnsys_mock_kernel", "");
+
+        call_type(CallPhase::VersionSigning);
+        call_type(CallPhase::FirstCall);
+        call_type(CallPhase::StartTime);
+        for step in 1..=3 {
+            with_mock_kernel(|kernel| kernel.inputs[0] = step as f64);
+            call_type(CallPhase::Iteration);
+            assert_eq!(
+                with_mock_kernel(|kernel| kernel.outputs[0]),
+                3. * step as f64
+            );
+            call_type(CallPhase::EndOfTimestep);
+            with_mock_kernel(MockKernel::advance_timestep);
+        }
+        call_type(CallPhase::LastCall);
+
+        with_mock_kernel(|kernel| {
+            assert_eq!(kernel.declared.type_version, Some(TRNSYS_VERSION));
+            assert_eq!(kernel.declared.params, Some(1));
+            assert_eq!(kernel.declared.outputs, Some(1));
+            assert!(!kernel.error_found, "{:?}", kernel.messages);
+        });
+    }
+
+    #[test]
+    #[cfg(mock_kernel)]
+    fn aborted_simulation_ends_when_the_next_one_starts() {
+        use crate::trnsys::mock_kernel::{call_type, with_mock_kernel};
+
+        let _simulation = mock_simulation("trnsys_mock_kernel_aborted", "");
+
+        // aborted after the first iteration, without the last call
+        call_type(CallPhase::VersionSigning);
+        call_type(CallPhase::FirstCall);
+        call_type(CallPhase::StartTime);
+        with_mock_kernel(|kernel| kernel.inputs[0] = 5.);
+        call_type(CallPhase::Iteration);
+
+        call_type(CallPhase::VersionSigning);
+        call_type(CallPhase::FirstCall);
+        call_type(CallPhase::StartTime);
+        with_mock_kernel(|kernel| kernel.inputs[0] = 1.);
+        call_type(CallPhase::Iteration);
+        call_type(CallPhase::LastCall);
+
+        with_mock_kernel(|kernel| {
+            assert_eq!(kernel.outputs[0], 3.);
+            assert!(
+                kernel
+                    .messages
+                    .iter()
+                    .any(|m| m.message.contains("previous simulation was aborted")),
+                "{:?}",
+                kernel.messages
+            );
+            assert!(!kernel.error_found, "{:?}", kernel.messages);
+        });
+    }
+
+    #[test]
+    #[cfg(mock_kernel)]
+    fn log_file_falls_back_to_the_temp_dir() {
+        use crate::trnsys::mock_kernel::{call_type, with_mock_kernel};
+
+        // the log directory cannot be created below a file
+        let _simulation = mock_simulation("trnsys_mock_kernel_blocked", "dir = \"blocker/logs\"");
+        let deck_dir = std::env::temp_dir().join("trnsys_mock_kernel_blocked");
+        std::fs::write(deck_dir.join("blocker"), "").unwrap();
+
+        call_type(CallPhase::VersionSigning);
+        call_type(CallPhase::FirstCall);
+        call_type(CallPhase::StartTime);
+        call_type(CallPhase::Iteration);
+        call_type(CallPhase::LastCall);
+
+        with_mock_kernel(|kernel| {
+            assert_eq!(kernel.outputs[0], 3.);
+            assert!(
+                kernel
+                    .messages
+                    .iter()
+                    .any(|m| m.message.contains("Cannot create the log file in")),
+                "{:?}",
+                kernel.messages
+            );
+            assert!(!kernel.error_found, "{:?}", kernel.messages);
+        });
+    }
+
+    #[test]
+    #[cfg(mock_kernel)]
+    fn unit_out_of_range_is_reported() {
+        use crate::trnsys::mock_kernel::{call_type, with_mock_kernel};
+
+        let _simulation = mock_simulation("trnsys_mock_kernel_unit_range", "");
+        with_mock_kernel(|kernel| kernel.unit = 70_000);
+
+        call_type(CallPhase::VersionSigning);
+
+        with_mock_kernel(|kernel| {
+            assert!(kernel.error_found);
+            assert!(kernel.messages[0]
+                .message
+                .contains("Unit number 70000 is out of range"));
+            assert_eq!(kernel.messages[0].unit, 70_000);
+        });
+    }
+
+    #[test]
+    #[cfg(mock_kernel)]
+    fn fatal_errors_are_written_once_to_the_list_file() {
+        use crate::trnsys::mock_kernel::{call_type, with_mock_kernel};
+
+        let _simulation = mock_simulation("trnsys_mock_kernel_mismatch", "");
+        with_mock_kernel(|kernel| kernel.inputs = vec![1., 2.]);
+
+        call_type(CallPhase::VersionSigning);
+        call_type(CallPhase::FirstCall);
+        call_type(CallPhase::LastCall);
+
+        let (error_found, messages) =
+            with_mock_kernel(|kernel| (kernel.error_found, kernel.messages.clone()));
+        assert!(error_found);
+        let mismatches = messages
+            .iter()
+            .filter(|m| m.message.contains("The deck gives 2 inputs"))
+            .count();
+        assert_eq!(mismatches, 1, "{:?}", messages);
+    }
 }
@@ -0,0 +1,182 @@
+use crate::logging::{close_log_file, init_tracing};
+use crate::trnsys::error::TrnSysError;
+use crate::trnsys::list_file_limiter::log_suppressed_messages;
+use crate::trnsys::log_config::LogConfig;
+use crate::trnsys::{simulation_has_error, CallContext, CallPhase, TrnSysState};
+use crate::trnsys_type::TrnSysType;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use tracing::warn;
+
+/// The units in a chunk of the unit table.
+const UNIT_CHUNK: usize = 256;
+/// The number of chunks of the unit table, unit numbers range up to `UNIT_CHUNK * UNIT_CHUNKS`.
+const UNIT_CHUNKS: usize = 256;
+
+type UnitSlot = Mutex<Option<UnitEntry>>;
+
+/// The units of the process, indexed by unit number. A chunk is allocated when one of its units is
+/// called for the first time, so that a unit is found without hashing or a global lock. Each unit
+/// has a lock of its own, which is uncontended as the kernel calls one unit at a time.
+static UNITS: [OnceLock<Box<[UnitSlot]>>; UNIT_CHUNKS] = [const { OnceLock::new() }; UNIT_CHUNKS];
+
+/// The id of the running session, 0 if none is running. Ids start at 1.
+static SESSION_ID: AtomicU64 = AtomicU64::new(0);
+static LAST_SESSION_ID: AtomicU64 = AtomicU64::new(0);
+
+static SESSION: Mutex<Option<SimulationSession>> = Mutex::new(None);
+
+/// Everything that lives from the first call to the last call of a simulation.
+///
+/// Hosts like Simulation Studio or parametric drivers load the DLL once and run several
+/// simulations, so nothing may outlive the simulation it belongs to.
+struct SimulationSession {
+    id: u64,
+    type_instance: Arc<TrnSysType>,
+    /// The slots of the units which joined the session, cleared at the end of the session.
+    units: Vec<usize>,
+    finished_units: usize,
+}
+
+impl SimulationSession {
+    /// Starts a session for the first unit called: (re)opens the log files and creates the Type
+    /// instance.
+    fn start(id: u64, unit: i32) -> Self {
+        let (config, mut problems) = LogConfig::load(unit);
+        problems.extend(init_tracing(config));
+        for problem in problems {
+            warn!("{}", problem);
+        }
+        SimulationSession {
+            id,
+            type_instance: Arc::new(TrnSysType::new()),
+            units: vec![],
+            finished_units: 0,
+        }
+    }
+}
+
+/// A unit as seen by the running session.
+pub(crate) struct UnitEntry {
+    session: u64,
+    pub type_instance: Arc<TrnSysType>,
+    pub state: TrnSysState,
+}
+
+/// Exclusive access to the entry of a unit for the duration of a kernel call.
+pub(crate) struct UnitGuard(MutexGuard<'static, Option<UnitEntry>>);
+
+impl Deref for UnitGuard {
+    type Target = UnitEntry;
+
+    fn deref(&self) -> &UnitEntry {
+        self.0.as_ref().expect("unit joined the session")
+    }
+}
+
+impl DerefMut for UnitGuard {
+    fn deref_mut(&mut self) -> &mut UnitEntry {
+        self.0.as_mut().expect("unit joined the session")
+    }
+}
+
+/// The index of the slot of a unit in the unit table.
+fn unit_index(unit: i32) -> Result<usize, TrnSysError> {
+    usize::try_from(unit)
+        .ok()
+        .filter(|&index| index < UNIT_CHUNK * UNIT_CHUNKS)
+        .ok_or(TrnSysError::UnitOutOfRange {
+            unit,
+            max: UNIT_CHUNK * UNIT_CHUNKS - 1,
+        })
+}
+
+fn unit_slot(index: usize) -> &'static UnitSlot {
+    let chunk = UNITS[index / UNIT_CHUNK]
+        .get_or_init(|| (0..UNIT_CHUNK).map(|_| Mutex::new(None)).collect());
+    &chunk[index % UNIT_CHUNK]
+}
+
+/// Returns the entry of the unit, starting a session if none is running.
+///
+/// Once a unit has joined the running session, only the lock of its own entry is taken. The
+/// entry of a unit left over from a previous session is reset with a fresh state.
+///
+/// The kernel signs the version of a unit once per simulation, so a unit of the running session
+/// called for it again starts a new simulation: the previous one was aborted before the last call
+/// of every unit, and its session is torn down as failed.
+pub(crate) fn enter_unit(ctx: &CallContext) -> Result<UnitGuard, TrnSysError> {
+    let index = unit_index(ctx.unit)?;
+    let mut entry = unit_slot(index).lock().unwrap_or_else(|e| e.into_inner());
+    let session = SESSION_ID.load(Ordering::Acquire);
+    let joined = entry.as_ref().map(|entry| entry.session) == Some(session);
+    if joined && ctx.phase == CallPhase::VersionSigning {
+        end_session(ctx, session, true);
+    }
+    if !joined || ctx.phase == CallPhase::VersionSigning {
+        *entry = Some(join_session(ctx.unit, index));
+    }
+    Ok(UnitGuard(entry))
+}
+
+fn join_session(unit: i32, index: usize) -> UnitEntry {
+    let mut session = SESSION.lock().unwrap_or_else(|e| e.into_inner());
+    let session = session.get_or_insert_with(|| {
+        let id = LAST_SESSION_ID.fetch_add(1, Ordering::Relaxed) + 1;
+        let session = SimulationSession::start(id, unit);
+        SESSION_ID.store(id, Ordering::Release);
+        session
+    });
+    session.units.push(index);
+    UnitEntry {
+        session: session.id,
+        type_instance: session.type_instance.clone(),
+        state: TrnSysState::new(),
+    }
+}
+
+/// Records the last call of a unit. After the last call of every unit, the session is torn
+/// down: the log files are closed, and the units get a fresh state in the next simulation.
+pub(crate) fn finish_unit(ctx: &CallContext) {
+    let mut session = SESSION.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(running) = session.as_mut() else {
+        return;
+    };
+    running.finished_units += 1;
+    if running.finished_units < running.units.len() {
+        return;
+    }
+    let id = running.id;
+    drop(session);
+    end_session(ctx, id, simulation_has_error());
+}
+
+/// Tears down the session `id` if it is still running: the list file summary is written, the log
+/// files are closed, with the retention policy of a failed run if `has_error`, and the entries of
+/// its units are freed. An entry in use by a call, like the one of the unit which starts the next
+/// simulation, is left to be reset by its call.
+fn end_session(ctx: &CallContext, id: u64, has_error: bool) {
+    let mut session = SESSION.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(running) = session.take_if(|running| running.id == id) else {
+        return;
+    };
+    SESSION_ID.store(0, Ordering::Release);
+    drop(session);
+
+    for &index in &running.units {
+        if let Ok(mut entry) = unit_slot(index).try_lock() {
+            entry.take_if(|entry| entry.session == id);
+        }
+    }
+    if running.finished_units < running.units.len() {
+        let _span = ctx.span().entered();
+        warn!(
+            "The previous simulation was aborted, {} of {} units were not called for the last time",
+            running.units.len() - running.finished_units,
+            running.units.len()
+        );
+    }
+    log_suppressed_messages(ctx.unit, ctx.type_number);
+    close_log_file(has_error, ctx.unit, ctx.type_number);
+}
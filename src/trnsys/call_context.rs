@@ -0,0 +1,260 @@
+use crate::trnsys::{
+    get_current_type, get_current_unit, get_simulation_time, get_simulation_time_step,
+    get_timestep_iteration, is_end_of_timestep, is_first_call_of_simulation,
+    is_last_call_of_simulation, is_reread_parameters, is_start_time, is_version_signing_time,
+};
+use tracing::{error_span, Span};
+
+/// The name of the span opened for each kernel call, see [`CallContext::span`].
+pub(crate) const CALL_SPAN_NAME: &str = "call";
+
+/// The reason why the TRNSYS kernel calls the Type.
+///
+/// The phase is determined once per kernel call, in the same order the kernel flags
+/// are evaluated by the TRNSYS C++ template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallPhase {
+    /// The kernel asks for the version the Type was written for.
+    VersionSigning,
+    /// The very first call of the simulation. Only the counts of parameters,
+    /// inputs and outputs are available.
+    FirstCall,
+    /// The call at the simulation start time, after the first call.
+    StartTime,
+    /// A regular iterative call within a time step.
+    Iteration,
+    /// A regular iterative call after the kernel requested the parameters to be reread.
+    RereadParameters,
+    /// The call after all Types have converged at the current time step.
+    EndOfTimestep,
+    /// The call before the simulation shuts down, whether it ended correctly or in error.
+    LastCall,
+}
+
+impl CallPhase {
+    /// All the phases, in the order of a simulation.
+    pub const ALL: [CallPhase; 7] = [
+        CallPhase::VersionSigning,
+        CallPhase::FirstCall,
+        CallPhase::StartTime,
+        CallPhase::Iteration,
+        CallPhase::RereadParameters,
+        CallPhase::EndOfTimestep,
+        CallPhase::LastCall,
+    ];
+
+    /// The name of the phase, as in the logs.
+    pub fn name(self) -> &'static str {
+        match self {
+            CallPhase::VersionSigning => "VersionSigning",
+            CallPhase::FirstCall => "FirstCall",
+            CallPhase::StartTime => "StartTime",
+            CallPhase::Iteration => "Iteration",
+            CallPhase::RereadParameters => "RereadParameters",
+            CallPhase::EndOfTimestep => "EndOfTimestep",
+            CallPhase::LastCall => "LastCall",
+        }
+    }
+
+    /// Queries the kernel flags to find out the phase of the current call.
+    pub fn from_kernel() -> Self {
+        Self::from_flags(|flag| match flag {
+            KernelFlag::VersionSigningTime => is_version_signing_time(),
+            KernelFlag::FirstCallOfSimulation => is_first_call_of_simulation(),
+            KernelFlag::LastCallOfSimulation => is_last_call_of_simulation(),
+            KernelFlag::EndOfTimestep => is_end_of_timestep(),
+            KernelFlag::StartTime => is_start_time(),
+            KernelFlag::RereadParameters => is_reread_parameters(),
+        })
+    }
+
+    /// Finds out the phase from the kernel flags. The flags are queried in order, and only until
+    /// one of them is raised.
+    pub fn from_flags(is_raised: impl Fn(KernelFlag) -> bool) -> Self {
+        if is_raised(KernelFlag::VersionSigningTime) {
+            CallPhase::VersionSigning
+        } else if is_raised(KernelFlag::FirstCallOfSimulation) {
+            CallPhase::FirstCall
+        } else if is_raised(KernelFlag::LastCallOfSimulation) {
+            CallPhase::LastCall
+        } else if is_raised(KernelFlag::EndOfTimestep) {
+            CallPhase::EndOfTimestep
+        } else if is_raised(KernelFlag::StartTime) {
+            CallPhase::StartTime
+        } else if is_raised(KernelFlag::RereadParameters) {
+            CallPhase::RereadParameters
+        } else {
+            CallPhase::Iteration
+        }
+    }
+
+    /// Whether the user's `iterate` is called in this phase.
+    pub fn is_iterative(&self) -> bool {
+        matches!(self, CallPhase::Iteration | CallPhase::RereadParameters)
+    }
+}
+
+/// A flag the kernel raises to tell the Type why it is called, see [`CallPhase::from_flags`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KernelFlag {
+    VersionSigningTime,
+    FirstCallOfSimulation,
+    LastCallOfSimulation,
+    EndOfTimestep,
+    StartTime,
+    RereadParameters,
+}
+
+/// Everything the kernel tells about the current call, captured once when the call starts.
+///
+/// The context is passed to every callback of the Type, so that user code doesn't need
+/// to query the kernel again. It can be built by hand with [`CallContext::new`] in tests.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CallContext {
+    pub phase: CallPhase,
+    /// Current simulation time in hours.
+    pub time: f64,
+    /// Simulation time step in hours.
+    pub timestep: f64,
+    /// Iteration number within the current time step.
+    pub iteration: i32,
+    /// Unit number of the called Type.
+    pub unit: i32,
+    /// Type number of the called Type.
+    pub type_number: i32,
+}
+
+impl CallContext {
+    pub fn new(
+        phase: CallPhase,
+        time: f64,
+        timestep: f64,
+        iteration: i32,
+        unit: i32,
+        type_number: i32,
+    ) -> Self {
+        CallContext {
+            phase,
+            time,
+            timestep,
+            iteration,
+            unit,
+            type_number,
+        }
+    }
+
+    /// Reads the phase, time and identity of the current call from the kernel.
+    pub fn from_kernel() -> Self {
+        CallContext {
+            phase: CallPhase::from_kernel(),
+            time: get_simulation_time(),
+            timestep: get_simulation_time_step(),
+            iteration: get_timestep_iteration(),
+            unit: get_current_unit(),
+            type_number: get_current_type(),
+        }
+    }
+
+    /// A span carrying the unit, type, phase, simulation time and iteration of the call.
+    ///
+    /// `entrance()` enters it for the whole call, so that every event is tagged with its call.
+    /// Work spawned onto other threads should enter it too:
+    ///
+    /// ```ignore
+    /// let span = Span::current();
+    /// std::thread::spawn(move || span.in_scope(|| info!("tagged with the unit")));
+    /// ```
+    pub fn span(&self) -> Span {
+        // at the error level, so that the call context is never filtered out
+        error_span!(
+            CALL_SPAN_NAME,
+            unit = self.unit,
+            type_number = self.type_number,
+            phase = ?self.phase,
+            time = self.time,
+            iteration = self.iteration
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phase(raised: &[KernelFlag]) -> CallPhase {
+        CallPhase::from_flags(|flag| raised.contains(&flag))
+    }
+
+    #[test]
+    fn derives_the_phase_from_the_kernel_flags() {
+        assert_eq!(
+            phase(&[KernelFlag::VersionSigningTime]),
+            CallPhase::VersionSigning
+        );
+        assert_eq!(
+            phase(&[KernelFlag::FirstCallOfSimulation]),
+            CallPhase::FirstCall
+        );
+        assert_eq!(phase(&[KernelFlag::StartTime]), CallPhase::StartTime);
+        assert_eq!(phase(&[]), CallPhase::Iteration);
+        assert_eq!(
+            phase(&[KernelFlag::RereadParameters]),
+            CallPhase::RereadParameters
+        );
+        assert_eq!(
+            phase(&[KernelFlag::EndOfTimestep]),
+            CallPhase::EndOfTimestep
+        );
+        assert_eq!(
+            phase(&[KernelFlag::LastCallOfSimulation]),
+            CallPhase::LastCall
+        );
+    }
+
+    #[test]
+    fn earlier_flags_take_precedence() {
+        // the kernel also reports the end of the time step at the last call
+        let last_call = [KernelFlag::LastCallOfSimulation, KernelFlag::EndOfTimestep];
+        assert_eq!(phase(&last_call), CallPhase::LastCall);
+        let start = [KernelFlag::StartTime, KernelFlag::RereadParameters];
+        assert_eq!(phase(&start), CallPhase::StartTime);
+        let signing = [
+            KernelFlag::VersionSigningTime,
+            KernelFlag::FirstCallOfSimulation,
+        ];
+        assert_eq!(phase(&signing), CallPhase::VersionSigning);
+    }
+
+    #[test]
+    fn queries_the_flags_only_until_one_is_raised() {
+        let queried = std::cell::RefCell::new(Vec::new());
+        let phase = CallPhase::from_flags(|flag| {
+            queried.borrow_mut().push(flag);
+            flag == KernelFlag::FirstCallOfSimulation
+        });
+        assert_eq!(phase, CallPhase::FirstCall);
+        assert_eq!(
+            *queried.borrow(),
+            [
+                KernelFlag::VersionSigningTime,
+                KernelFlag::FirstCallOfSimulation
+            ]
+        );
+    }
+
+    #[test]
+    fn only_iterations_call_iterate() {
+        assert!(CallPhase::Iteration.is_iterative());
+        assert!(CallPhase::RereadParameters.is_iterative());
+        assert!(!CallPhase::StartTime.is_iterative());
+        assert!(!CallPhase::EndOfTimestep.is_iterative());
+    }
+
+    #[test]
+    fn builds_contexts_by_hand() {
+        let ctx = CallContext::new(CallPhase::EndOfTimestep, 2.5, 0.25, 3, 12, 256);
+        assert_eq!(ctx.phase, CallPhase::EndOfTimestep);
+        assert_eq!((ctx.time, ctx.timestep), (2.5, 0.25));
+        assert_eq!((ctx.iteration, ctx.unit, ctx.type_number), (3, 12, 256));
+    }
+}
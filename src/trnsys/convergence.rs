@@ -0,0 +1,271 @@
+use crate::trnsys::param::TrnSysValue;
+use crate::trnsys::CallContext;
+use tracing::{info, warn};
+
+/// Number of worst outputs listed when a time step needed too many iterations.
+const WORST_OFFENDERS: usize = 3;
+
+/// Convergence history of a single output within the current time step.
+#[derive(Debug, Clone, Default)]
+struct OutputHistory {
+    last_value: f64,
+    last_delta: f64,
+    /// Number of times the change between iterations switched its sign while being
+    /// larger than the tolerance.
+    sign_changes: u32,
+}
+
+impl OutputHistory {
+    fn is_oscillating(&self) -> bool {
+        self.sign_changes >= 2
+    }
+}
+
+/// Iteration statistics of a unit over the whole simulation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IterationStatistics {
+    pub timesteps: u64,
+    pub iterations: u64,
+    pub max_iterations: u32,
+    /// Number of time steps which needed more iterations than the threshold.
+    pub slow_timesteps: u64,
+    /// Number of time steps at which at least one output oscillated.
+    pub oscillating_timesteps: u64,
+}
+
+impl IterationStatistics {
+    pub fn mean_iterations(&self) -> f64 {
+        if self.timesteps == 0 {
+            0.
+        } else {
+            self.iterations as f64 / self.timesteps as f64
+        }
+    }
+}
+
+/// Tracks how the outputs of a unit change between the iterations of a time step,
+/// to tell which unit keeps a time step from converging.
+#[derive(Debug, Clone)]
+pub struct ConvergenceTracker {
+    /// The worst outputs are logged at the end of a time step which needed more
+    /// iterations than this.
+    pub iteration_threshold: u32,
+    /// The kernel's convergence tolerance: relative if positive, absolute if negative.
+    pub tolerance: f64,
+    time: Option<f64>,
+    /// The highest iteration number the kernel reported at the current time step.
+    iterations: u32,
+    outputs: Vec<OutputHistory>,
+    statistics: IterationStatistics,
+}
+
+impl Default for ConvergenceTracker {
+    fn default() -> Self {
+        ConvergenceTracker {
+            iteration_threshold: 10,
+            tolerance: 0.001,
+            time: None,
+            iterations: 0,
+            outputs: vec![],
+            statistics: IterationStatistics::default(),
+        }
+    }
+}
+
+impl ConvergenceTracker {
+    pub fn statistics(&self) -> &IterationStatistics {
+        &self.statistics
+    }
+
+    /// Records the outputs computed at an iteration of the current time step.
+    ///
+    /// The iterations are counted by the kernel's iteration number, `ctx.iteration`, so that
+    /// several calls within the same iteration, like cache hits or reread calls, count once.
+    pub fn record_iteration(&mut self, ctx: &CallContext, outputs: &[TrnSysValue]) {
+        let iteration = ctx.iteration.max(1) as u32;
+        if self.time != Some(ctx.time) || iteration < self.iterations {
+            // the end of the previous time step was not reported, or the time step is repeated
+            self.time = Some(ctx.time);
+            self.iterations = 0;
+        }
+        let first_iteration = self.iterations == 0;
+        self.iterations = self.iterations.max(iteration);
+
+        if first_iteration || self.outputs.len() != outputs.len() {
+            // nothing to compare with at the first iteration of a time step, the history is
+            // refilled in place to keep the iterations free of allocations
+            self.outputs.clear();
+            self.outputs
+                .extend(outputs.iter().map(|output| OutputHistory {
+                    last_value: output.value,
+                    ..OutputHistory::default()
+                }));
+            return;
+        }
+
+        let tolerance = self.tolerance;
+        for (history, output) in self.outputs.iter_mut().zip(outputs) {
+            let delta = output.value - history.last_value;
+            if exceeds_tolerance(delta, output.value, tolerance)
+                && exceeds_tolerance(history.last_delta, history.last_value, tolerance)
+                && delta.signum() != history.last_delta.signum()
+            {
+                history.sign_changes += 1;
+            }
+            history.last_delta = delta;
+            history.last_value = output.value;
+        }
+    }
+
+    /// Closes the current time step: updates the statistics and logs the outputs which
+    /// changed the most at the last iteration if the time step needed too many iterations.
+    pub fn end_of_timestep(&mut self, ctx: &CallContext) {
+        if self.iterations == 0 {
+            return;
+        }
+        let oscillating = self.outputs.iter().any(OutputHistory::is_oscillating);
+
+        let statistics = &mut self.statistics;
+        statistics.timesteps += 1;
+        statistics.iterations += self.iterations as u64;
+        statistics.max_iterations = statistics.max_iterations.max(self.iterations);
+        if oscillating {
+            statistics.oscillating_timesteps += 1;
+        }
+
+        if self.iterations > self.iteration_threshold {
+            statistics.slow_timesteps += 1;
+
+            let mut worst: Vec<(usize, &OutputHistory)> = self.outputs.iter().enumerate().collect();
+            worst.sort_by(|(_, a), (_, b)| relative_change(b).total_cmp(&relative_change(a)));
+            let offenders = worst
+                .iter()
+                .take(WORST_OFFENDERS)
+                .map(|(i, history)| {
+                    format!(
+                        "output {} = {} (last change {:+e}{})",
+                        i + 1,
+                        history.last_value,
+                        history.last_delta,
+                        if history.is_oscillating() {
+                            ", oscillating"
+                        } else {
+                            ""
+                        }
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            warn!(
+                "Unit {} needed {} iterations at t={}, worst outputs: {}",
+                ctx.unit, self.iterations, ctx.time, offenders
+            );
+        }
+
+        self.time = None;
+        self.iterations = 0;
+    }
+
+    /// Logs the iteration statistics of the unit, called at the end of the simulation.
+    pub fn log_summary(&self, unit: i32) {
+        let statistics = &self.statistics;
+        info!(
+            "Unit {} converged {} time steps with {} iterations (mean {:.2}, max {}), \
+            {} time steps above {} iterations, {} time steps with oscillating outputs",
+            unit,
+            statistics.timesteps,
+            statistics.iterations,
+            statistics.mean_iterations(),
+            statistics.max_iterations,
+            statistics.slow_timesteps,
+            self.iteration_threshold,
+            statistics.oscillating_timesteps
+        );
+    }
+}
+
+/// Whether the change `delta` of an output with the value `value` is larger than the tolerance.
+fn exceeds_tolerance(delta: f64, value: f64, tolerance: f64) -> bool {
+    if tolerance < 0. {
+        delta.abs() > -tolerance
+    } else {
+        delta.abs() > tolerance * value.abs().max(f64::EPSILON)
+    }
+}
+
+fn relative_change(history: &OutputHistory) -> f64 {
+    history.last_delta.abs() / history.last_value.abs().max(f64::EPSILON)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trnsys::CallPhase;
+
+    fn ctx(time: f64, iteration: i32) -> CallContext {
+        CallContext::new(CallPhase::Iteration, time, 1., iteration, 1, 256)
+    }
+
+    /// Records the outputs of the iterations 1, 2, ... of the time step `time`, and closes it.
+    fn timestep(tracker: &mut ConvergenceTracker, time: f64, outputs: &[f64]) {
+        for (i, &output) in outputs.iter().enumerate() {
+            tracker.record_iteration(&ctx(time, i as i32 + 1), &[TrnSysValue::new(output)]);
+        }
+        tracker.end_of_timestep(&ctx(time, outputs.len() as i32));
+    }
+
+    #[test]
+    fn detects_oscillating_outputs() {
+        let mut tracker = ConvergenceTracker::default();
+        timestep(&mut tracker, 1., &[10., 12., 10., 12.]);
+        timestep(&mut tracker, 2., &[10., 11., 11.5, 11.5]);
+        let statistics = tracker.statistics();
+        assert_eq!(statistics.oscillating_timesteps, 1);
+        assert_eq!(statistics.timesteps, 2);
+        assert_eq!(statistics.iterations, 8);
+        assert_eq!(statistics.max_iterations, 4);
+        assert_eq!(statistics.mean_iterations(), 4.);
+    }
+
+    #[test]
+    fn changes_within_the_tolerance_do_not_oscillate() {
+        let mut tracker = ConvergenceTracker::default();
+        timestep(&mut tracker, 1., &[10., 10.001, 10., 10.001]);
+        assert_eq!(tracker.statistics().oscillating_timesteps, 0);
+        // an absolute tolerance
+        tracker.tolerance = -0.0001;
+        timestep(&mut tracker, 2., &[10., 10.001, 10., 10.001]);
+        assert_eq!(tracker.statistics().oscillating_timesteps, 1);
+    }
+
+    #[test]
+    fn counts_the_kernel_iterations() {
+        let mut tracker = ConvergenceTracker::default();
+        let output = [TrnSysValue::new(1.)];
+        // a cache hit or a reread call in the same iteration counts once
+        tracker.record_iteration(&ctx(1., 1), &output);
+        tracker.record_iteration(&ctx(1., 2), &output);
+        tracker.record_iteration(&ctx(1., 2), &output);
+        tracker.end_of_timestep(&ctx(1., 2));
+        // a time step whose end was not reported
+        tracker.record_iteration(&ctx(2., 1), &output);
+        tracker.record_iteration(&ctx(3., 1), &output);
+        tracker.end_of_timestep(&ctx(3., 1));
+        let statistics = tracker.statistics();
+        assert_eq!((statistics.timesteps, statistics.iterations), (2, 3));
+    }
+
+    #[test]
+    fn counts_slow_timesteps() {
+        let mut tracker = ConvergenceTracker {
+            iteration_threshold: 2,
+            ..ConvergenceTracker::default()
+        };
+        timestep(&mut tracker, 1., &[1., 2.]);
+        timestep(&mut tracker, 2., &[1., 2., 3.]);
+        assert_eq!(tracker.statistics().slow_timesteps, 1);
+        // nothing recorded, nothing counted
+        tracker.end_of_timestep(&ctx(3., 1));
+        assert_eq!(tracker.statistics().timesteps, 2);
+    }
+}
@@ -0,0 +1,311 @@
+use crate::trnsys::error::{InputError, TrnSysError};
+use crate::trnsys::param::TrnSysValue;
+use crate::trnsys::{InputIndex, OutputIndex, ParamIndex, TrnSysState};
+use std::fmt::Write;
+
+/// A block of consecutive parameters, inputs, outputs or derivatives.
+#[derive(Debug, Clone)]
+enum Block {
+    Fixed(usize),
+    /// `size` entries repeated as many times as the parameter `repeat` says, like a cycle of a
+    /// proforma, e.g. the inlet temperature and flow rate of each of the N nodes.
+    Cycle {
+        name: &'static str,
+        size: usize,
+        repeat: ParamIndex,
+    },
+    /// A count computed from the parameters.
+    Formula {
+        name: &'static str,
+        count: fn(&[TrnSysValue]) -> Result<usize, TrnSysError>,
+    },
+}
+
+/// How the parameters, inputs, outputs or derivatives of a Type are laid out, as a sequence of
+/// blocks whose sizes may depend on the parameters:
+///
+/// ```ignore
+/// // 1 parameter for the number of nodes, then a height per node
+/// Layout::fixed(1).cycle("nodes", 1, ParamIndex(0))
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Layout {
+    blocks: Vec<Block>,
+}
+
+impl Layout {
+    pub fn fixed(count: usize) -> Self {
+        Layout::default().then(count)
+    }
+
+    /// Appends `count` entries.
+    pub fn then(mut self, count: usize) -> Self {
+        self.blocks.push(Block::Fixed(count));
+        self
+    }
+
+    /// Appends a group of `size` entries, repeated as many times as the parameter `repeat` says.
+    /// A cycle of parameters must be governed by a parameter before it.
+    pub fn cycle(mut self, name: &'static str, size: usize, repeat: ParamIndex) -> Self {
+        self.blocks.push(Block::Cycle { name, size, repeat });
+        self
+    }
+
+    /// Appends a block whose size is computed from the parameters. In the layout of the parameters,
+    /// the formula only sees the parameters before the block.
+    pub fn formula(
+        mut self,
+        name: &'static str,
+        count: fn(&[TrnSysValue]) -> Result<usize, TrnSysError>,
+    ) -> Self {
+        self.blocks.push(Block::Formula { name, count });
+        self
+    }
+
+    /// Computes the blocks from the parameters, and explains the computation, e.g.
+    /// `2 + 2 × 3 nodes (parameter 1)`.
+    fn size(
+        &self,
+        kind: &'static str,
+        params: &[TrnSysValue],
+    ) -> Result<(SizedLayout, String), TrnSysError> {
+        let mut sized = SizedLayout::default();
+        let mut explanation = String::new();
+        for block in &self.blocks {
+            // parameters can only govern the parameters after them
+            let known = if kind == ParamIndex::KIND {
+                &params[..sized.count.min(params.len())]
+            } else {
+                params
+            };
+            if !explanation.is_empty() {
+                explanation.push_str(" + ");
+            }
+            match *block {
+                Block::Fixed(count) => {
+                    sized.count += count;
+                    let _ = write!(explanation, "{}", count);
+                }
+                Block::Cycle { name, size, repeat } => {
+                    let repeat_value = known.get(repeat.0).ok_or_else(|| {
+                        TrnSysError::GeneralError(format!(
+                            "The number of {} is given by {}, which is not available{}",
+                            name,
+                            repeat,
+                            if kind == ParamIndex::KIND {
+                                " before the cycle"
+                            } else {
+                                ""
+                            }
+                        ))
+                    })?;
+                    let repeat_count = i32::try_from(repeat_value)
+                        .ok()
+                        .and_then(|count| usize::try_from(count).ok())
+                        .ok_or(InputError::BadParameter {
+                            index: repeat,
+                            message: format!(
+                                "{} = {} sets the number of {}, it must be a non-negative integer",
+                                repeat, repeat_value.value, name
+                            ),
+                        })?;
+                    sized.cycles.push(SizedCycle {
+                        name,
+                        start: sized.count,
+                        size,
+                        repeat: repeat_count,
+                    });
+                    sized.count += size * repeat_count;
+                    let _ = write!(
+                        explanation,
+                        "{} × {} {} ({})",
+                        size, repeat_count, name, repeat
+                    );
+                }
+                Block::Formula { name, count } => {
+                    let count = count(known)?;
+                    sized.count += count;
+                    let _ = write!(explanation, "{} {}", count, name);
+                }
+            }
+        }
+        if explanation.is_empty() {
+            explanation.push('0');
+        }
+        Ok((sized, explanation))
+    }
+}
+
+/// The sizing of a Type, declared by `TrnSysType::dimensions` and computed at the first call of
+/// the simulation from the parameters in the deck.
+#[derive(Debug, Clone, Default)]
+pub struct Dimensions {
+    pub params: Layout,
+    pub inputs: Layout,
+    pub outputs: Layout,
+    pub derivatives: Layout,
+}
+
+impl Dimensions {
+    /// Computes the counts from the parameters and checks them against the counts of the deck,
+    /// which are in `state` at the first call.
+    pub fn size(&self, state: &TrnSysState, params: &[TrnSysValue]) -> Result<Sizes, TrnSysError> {
+        let check = |layout: &Layout, kind: &'static str, deck: i32| {
+            let (sized, explanation) = layout.size(kind, params)?;
+            if sized.count as i32 != deck {
+                return Err(TrnSysError::CountMismatch {
+                    kind,
+                    deck,
+                    expected: sized.count,
+                    explanation,
+                });
+            }
+            Ok(sized)
+        };
+        Ok(Sizes {
+            params: check(&self.params, ParamIndex::KIND, state.num_params)?,
+            inputs: check(&self.inputs, InputIndex::KIND, state.num_inputs)?,
+            outputs: check(&self.outputs, OutputIndex::KIND, state.num_outputs)?,
+            derivatives: check(&self.derivatives, "derivative", state.num_derivatives)?,
+        })
+    }
+}
+
+/// A cycle of a layout, with its position once the number of repetitions is known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizedCycle {
+    pub name: &'static str,
+    /// The index of the first entry of the cycle.
+    pub start: usize,
+    /// The number of entries of one repetition.
+    pub size: usize,
+    pub repeat: usize,
+}
+
+impl SizedCycle {
+    /// The index of the entry `offset` of the repetition `repetition`, both 0-based.
+    pub fn index(&self, repetition: usize, offset: usize) -> usize {
+        debug_assert!(repetition < self.repeat && offset < self.size);
+        self.start + repetition * self.size + offset
+    }
+}
+
+/// A layout with the sizes of its blocks computed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SizedLayout {
+    pub count: usize,
+    pub cycles: Vec<SizedCycle>,
+}
+
+impl SizedLayout {
+    /// Looks up a cycle by the name it was declared with.
+    pub fn cycle(&self, name: &str) -> Option<&SizedCycle> {
+        self.cycles.iter().find(|cycle| cycle.name == name)
+    }
+}
+
+/// The counts computed in the sizing phase, see [`Dimensions`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sizes {
+    pub params: SizedLayout,
+    pub inputs: SizedLayout,
+    pub outputs: SizedLayout,
+    pub derivatives: SizedLayout,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(values: &[f64]) -> Vec<TrnSysValue> {
+        values.iter().copied().map(TrnSysValue::new).collect()
+    }
+
+    fn state(params: i32, inputs: i32) -> TrnSysState {
+        let mut state = TrnSysState::new();
+        state.num_params = params;
+        state.num_inputs = inputs;
+        state
+    }
+
+    /// 1 parameter for the number of nodes, then 2 inputs per node after a fixed one.
+    fn nodes() -> Dimensions {
+        Dimensions {
+            params: Layout::fixed(1),
+            inputs: Layout::fixed(1).cycle("nodes", 2, ParamIndex(0)),
+            ..Dimensions::default()
+        }
+    }
+
+    #[test]
+    fn sizes_cycles_from_the_parameters() {
+        let sizes = nodes().size(&state(1, 7), &values(&[3.])).unwrap();
+        assert_eq!(sizes.inputs.count, 7);
+        let cycle = sizes.inputs.cycle("nodes").unwrap();
+        assert_eq!((cycle.start, cycle.size, cycle.repeat), (1, 2, 3));
+        assert_eq!(cycle.index(2, 1), 6);
+        assert!(sizes.inputs.cycle("layers").is_none());
+    }
+
+    #[test]
+    fn explains_a_count_mismatch() {
+        let error = nodes().size(&state(1, 6), &values(&[3.])).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The deck gives 6 inputs, but the Type expects 7 = 1 + 2 × 3 nodes (parameter 1)"
+        );
+        let error = Dimensions::default().size(&state(1, 0), &[]).unwrap_err();
+        assert!(error.to_string().ends_with("expects 0 = 0"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_repeat_counts_which_are_not_counts() {
+        for repeat in [f64::NAN, 2.5, -1.] {
+            let error = nodes().size(&state(1, 7), &values(&[repeat])).unwrap_err();
+            assert!(
+                matches!(
+                    error,
+                    TrnSysError::InputError(InputError::BadParameter {
+                        index: ParamIndex(0),
+                        ..
+                    })
+                ),
+                "{:?}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn parameter_cycles_are_governed_by_earlier_parameters() {
+        let dimensions = Dimensions {
+            params: Layout::fixed(1).cycle("layers", 1, ParamIndex(1)).then(1),
+            ..Dimensions::default()
+        };
+        let error = dimensions
+            .size(&state(3, 0), &values(&[1., 1., 1.]))
+            .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .ends_with("which is not available before the cycle"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn sizes_formulas_from_the_parameters() {
+        let dimensions = Dimensions {
+            params: Layout::fixed(2),
+            outputs: Layout::default().formula("cells", |params| {
+                Ok(i32::try_from(&params[0])? as usize * i32::try_from(&params[1])? as usize)
+            }),
+            ..Dimensions::default()
+        };
+        let mut state = state(2, 0);
+        state.num_outputs = 6;
+        let sizes = dimensions.size(&state, &values(&[2., 3.])).unwrap();
+        assert_eq!(sizes.outputs.count, 6);
+    }
+}
@@ -1,9 +1,15 @@
-use crate::trnsys::{found_bad_input, found_bad_parameter, Severity, TrnSysState};
+use crate::trnsys::unit_catalog::{Dimension, TrnSysUnit};
+use crate::trnsys::{
+    found_bad_input, found_bad_parameter, log_message, CallContext, CallPhase, InputIndex,
+    OutputIndex, ParamIndex, Severity, TrnSysState,
+};
 use thiserror::Error;
 use tracing::error;
 
 pub trait TrnSysErrorHandler {
-    fn handle_in_trnsys(&self, state: &TrnSysState);
+    /// Reports the error to the kernel, and returns whether it was reported, so that the error
+    /// is not written to the list file a second time.
+    fn handle_in_trnsys(&self, ctx: &CallContext, state: &TrnSysState) -> bool;
 }
 
 #[derive(Error, Debug)]
@@ -14,15 +20,61 @@ pub enum TrnSysError {
     InputError(#[from] InputError),
     #[error("Cannot convert {param}: {message}")]
     ConversionError { param: String, message: String },
+    #[error("The {kind} index {index} is out of range, only {count} declared")]
+    IndexOutOfRange {
+        kind: &'static str,
+        index: usize,
+        count: i32,
+    },
+    #[error("`{function}` cannot be called during {phase:?}")]
+    IllegalPhase {
+        function: &'static str,
+        phase: CallPhase,
+    },
+    #[error("`{function}` was called outside of a kernel call")]
+    NoActiveCall { function: &'static str },
+    #[error("Expected a unit of {expected:?}, but the value is declared in {unit}")]
+    DimensionMismatch {
+        expected: Dimension,
+        unit: TrnSysUnit,
+    },
+    #[error("No unit is declared for {kind} {index}")]
+    UndeclaredUnit { kind: &'static str, index: usize },
+    #[error("The deck gives {deck} {kind}s, but the Type expects {expected} = {explanation}")]
+    CountMismatch {
+        kind: &'static str,
+        deck: i32,
+        expected: usize,
+        explanation: String,
+    },
+    #[error("Bad Output: {message}")]
+    BadOutput { index: OutputIndex, message: String },
+    #[error("Unknown TRNSYS unit code: {0}")]
+    UnknownUnitCode(String),
+    #[error("Cannot connect an output in {output} to an input in {input}")]
+    IncompatibleUnits {
+        output: TrnSysUnit,
+        input: TrnSysUnit,
+    },
+    #[error("Unit number {unit} is out of range, the Type supports units 0 to {max}")]
+    UnitOutOfRange { unit: i32, max: usize },
 }
 
 impl TrnSysErrorHandler for TrnSysError {
-    fn handle_in_trnsys(&self, state: &TrnSysState) {
+    fn handle_in_trnsys(&self, ctx: &CallContext, state: &TrnSysState) -> bool {
         match self {
-            TrnSysError::InputError(e) => {
-                e.handle_in_trnsys(state);
+            TrnSysError::InputError(e) => e.handle_in_trnsys(ctx, state),
+            TrnSysError::BadOutput { .. } | TrnSysError::CountMismatch { .. } => {
+                log_message(
+                    Severity::Fatal,
+                    -1,
+                    ctx.unit,
+                    ctx.type_number,
+                    &self.to_string(),
+                );
+                true
             }
-            _ => {}
+            _ => false,
         }
     }
 }
@@ -31,30 +83,32 @@ impl TrnSysErrorHandler for TrnSysError {
 #[derive(Error, Debug)]
 pub enum InputError {
     #[error("Bad Input at {index}: {message}")]
-    BadInput { index: i32, message: String },
+    BadInput { index: InputIndex, message: String },
     #[error("Bad Parameter at {index}: {message}")]
-    BadParameter { index: i32, message: String },
+    BadParameter { index: ParamIndex, message: String },
 }
 
 impl TrnSysErrorHandler for InputError {
-    fn handle_in_trnsys(&self, state: &TrnSysState) {
-        match self {
+    fn handle_in_trnsys(&self, ctx: &CallContext, state: &TrnSysState) -> bool {
+        let reported = match self {
             InputError::BadInput { index, message } => {
-                found_bad_input(index.clone(), Severity::Fatal, &format!("{:?}", self));
+                found_bad_input(*index, Severity::Fatal, &format!("{:?}", self))
             }
             InputError::BadParameter { index, message } => {
-                found_bad_parameter(index.clone(), Severity::Fatal, &format!("{:?}", self));
+                found_bad_parameter(*index, Severity::Fatal, &format!("{:?}", self))
             }
+        };
+        if let Err(e) = &reported {
+            error!("Cannot report {} to TrnSys: {}", self, e);
         }
+        reported.is_ok()
     }
 }
 
 impl TrnSysErrorHandler for anyhow::Error {
-    fn handle_in_trnsys(&self, state: &TrnSysState) {
-        if let Some(trnsys_err) = self.downcast_ref::<TrnSysError>() {
-            trnsys_err.handle_in_trnsys(state);
-        } else {
-            error!("Unhandled Application Error: {:?}", self);
-        }
+    fn handle_in_trnsys(&self, ctx: &CallContext, state: &TrnSysState) -> bool {
+        // other errors are only written to the list file by the caller
+        self.downcast_ref::<TrnSysError>()
+            .is_some_and(|trnsys_err| trnsys_err.handle_in_trnsys(ctx, state))
     }
 }
\ No newline at end of file
@@ -1,18 +1,28 @@
 #![allow(non_snake_case)]
 #![allow(non_camel_case_types)]
 #![allow(unused)]
+#![allow(clippy::upper_case_acronyms)]
 
 use std::os::raw::{c_char, c_double, c_int};
 
+/// Declares the kernel functions under their Intel Fortran names, linked to the symbols of the
+/// `fortran_mangling` of `Cargo.toml`, see `kernel_symbol!`.
+macro_rules! kernel_functions {
+    ($(pub fn $name:ident($($arg:ident: $ty:ty),* $(,)?) $(-> $ret:ty)?;)*) => {
+        extern "C" {
+            $(
+                #[link_name = kernel_symbol!($name)]
+                pub fn $name($($arg: $ty),*) $(-> $ret)?;
+            )*
+        }
+    };
+}
+
 //noinspection SpellCheckingInspection
-extern "C" {
+kernel_functions! {
     // --- Kernel subroutines ----------------------------------------------------------------------------------------------
     pub fn FOUNDBADINPUT(Input: *mut c_int, Severity: *mut c_char, Message: *mut c_char, Sevlen: usize, Messlen: usize);
     pub fn FOUNDBADPARAMETER(Param: *mut c_int, Severity: *mut c_char, Message: *mut c_char, Sevlen: usize, Messlen: usize);
-    pub fn INITREPORTINTEGRAL(index: *mut c_int, intName: *mut c_char, instUnit: *mut c_char, intUnit: *mut c_char, LenName: usize, LenUnit: usize, LUnit2: usize);
-    pub fn INITREPORTMINMAX(index: *mut c_int, minmaxName: *mut c_char, minmaxUnit: *mut c_char, LenName: usize, LenUnit: usize);
-    pub fn INITREPORTTEXT(index: *mut c_int, txtName: *mut c_char, txtVal: *mut c_char, LenName: usize, LenVal: usize);
-    pub fn INITREPORTVALUE(index: *mut c_int, valName: *mut c_char, valVal: *mut c_double, valUnit: *mut c_char, LenName: usize, LenUnit: usize);
     pub fn READNEXTCHAR(lun: *mut c_int) -> c_int;
     pub fn SETDESIREDDISCRETECONTROLSTATE(i: *mut c_int, j: *mut c_int);
     pub fn SETDYNAMICARRAYINITIALVALUE(i: *mut c_int, Value: *mut c_double);
@@ -25,7 +35,6 @@ extern "C" {
 
     pub fn SETNUMBEROFOUTPUTS(i: *mut c_int);
     pub fn SETNUMBEROFPARAMETERS(i: *mut c_int);
-    pub fn SETNUMBEROFREPORTVARIABLES(nInt: *mut c_int, nMinMax: *mut c_int, nVals: *mut c_int, nText: *mut c_int);
     pub fn SETNUMBERSTOREDVARIABLES(Nrequested_Static: *mut c_int, Nrequested_Dynamic: *mut c_int);
     pub fn SETNUMERICALDERIVATIVE(i: *mut c_int, Value: *mut c_double);
     pub fn SETOUTPUTUNITS(i: *mut c_int, String: *mut c_char, len: usize);
@@ -42,7 +51,6 @@ extern "C" {
     pub fn TRNSYSFUNCTIONS_mp_GETINPUTVALUE(i: *mut c_int) -> c_double;
     pub fn TRNSYSFUNCTIONS_mp_GETISENDOFTIMESTEP() -> c_int;
     pub fn TRNSYSFUNCTIONS_mp_GETISFIRSTCALLOFSIMULATION() -> c_int;
-    pub fn TRNSYSFUNCTIONS_mp_GETISINCLUDEDINSSR() -> c_int;
     pub fn TRNSYSFUNCTIONS_mp_GETISLASTCALLOFSIMULATION() -> c_int;
     pub fn TRNSYSFUNCTIONS_mp_GETISREREADPARAMETERS() -> c_int;
     pub fn TRNSYSFUNCTIONS_mp_GETISSTARTTIME() -> c_int;
@@ -71,8 +79,6 @@ extern "C" {
     pub fn TRNSYSFUNCTIONS_mp_GETTIMESTEPITERATION() -> c_int;
     pub fn TRNSYSFUNCTIONS_mp_GETTRNSYSINPUTFILEDIR(dir: *mut c_char, len: usize) -> *mut c_char;
     pub fn TRNSYSFUNCTIONS_mp_GETTRNSYSROOTDIR(dir: *mut c_char, len: usize) -> *mut c_char;
-    pub fn UPDATEREPORTINTEGRAL(index: *mut c_int, intVal: *mut c_double);
-    pub fn UPDATEREPORTMINMAX(index: *mut c_int, newVal: *mut c_double);
 
     //  --- TRNSYS subroutines  ---------------------------------------------------------------
 
@@ -84,4 +90,18 @@ extern "C" {
     pub fn MOISTAIRPROPERTIES(CurUnit: *mut c_int, CurType: *mut c_int, iunits: *mut c_int, mode: *mut c_int, wbmd: *mut c_int, psydat: *mut c_double, emode: *mut c_int, status: *mut c_int);
     pub fn SOLVEDIFFEQ(aa: *mut c_double, bb: *mut c_double, Ti: *mut c_double, Tf: *mut c_double, Tbar: *mut c_double);
     pub fn STEAM_PROPERTIES(units: *mut c_char, prop: *mut c_double, itype: *mut c_int, ierrst: *mut c_int, len: usize);
-}
\ No newline at end of file
+}
+
+// --- Kernel subroutines of TRNSYS 18 only ----------------------------------------------------------------------------
+// They are not imported, so that the DLL also loads in TRNSYS 17. See `kernel_version::kernel_function`.
+pub type INITREPORTINTEGRAL = unsafe extern "C" fn(index: *mut c_int, intName: *mut c_char, instUnit: *mut c_char, intUnit: *mut c_char, LenName: usize, LenUnit: usize, LUnit2: usize);
+pub type INITREPORTMINMAX = unsafe extern "C" fn(index: *mut c_int, minmaxName: *mut c_char, minmaxUnit: *mut c_char, LenName: usize, LenUnit: usize);
+pub type INITREPORTTEXT = unsafe extern "C" fn(index: *mut c_int, txtName: *mut c_char, txtVal: *mut c_char, LenName: usize, LenVal: usize);
+pub type INITREPORTVALUE = unsafe extern "C" fn(index: *mut c_int, valName: *mut c_char, valVal: *mut c_double, valUnit: *mut c_char, LenName: usize, LenUnit: usize);
+pub type SETNUMBEROFREPORTVARIABLES = unsafe extern "C" fn(nInt: *mut c_int, nMinMax: *mut c_int, nVals: *mut c_int, nText: *mut c_int);
+pub type TRNSYSFUNCTIONS_mp_GETISINCLUDEDINSSR = unsafe extern "C" fn() -> c_int;
+pub type UPDATEREPORTINTEGRAL = unsafe extern "C" fn(index: *mut c_int, intVal: *mut c_double);
+pub type UPDATEREPORTMINMAX = unsafe extern "C" fn(index: *mut c_int, newVal: *mut c_double);
+// --- Kernel functions of some builds only ------------------------------------------------------------------------------
+// Looked up like the functions of TRNSYS 18, and replaced by a default when the kernel doesn't export them.
+pub type TRNSYSFUNCTIONS_mp_GETMAXMESSAGELENGTH = unsafe extern "C" fn() -> c_int;
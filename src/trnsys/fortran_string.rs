@@ -0,0 +1,121 @@
+use std::os::raw::c_char;
+
+/// A string passed to the kernel as a Fortran `CHARACTER` argument.
+///
+/// Fortran doesn't read up to a NUL terminator, it reads exactly as many bytes as the hidden
+/// length argument says, so [`FortranString::len`] must be passed along with the pointer.
+/// A NUL terminator is kept after the content anyway for kernel routines written in C.
+pub(crate) struct FortranString {
+    bytes: Vec<u8>,
+}
+
+impl FortranString {
+    /// Marshals the string without a length limit.
+    ///
+    /// Interior NUL characters cannot be represented in the kernel and are replaced by blanks.
+    pub fn new(s: &str) -> Self {
+        let mut bytes: Vec<u8> = s.bytes().map(|b| if b == 0 { b' ' } else { b }).collect();
+        bytes.push(0);
+        FortranString { bytes }
+    }
+
+    /// Marshals the string and truncates it to at most `max_len` bytes,
+    /// without cutting a UTF-8 character in half.
+    pub fn with_max_len(s: &str, max_len: usize) -> Self {
+        FortranString::new(truncate_utf8(s, max_len))
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut c_char {
+        self.bytes.as_mut_ptr() as *mut c_char
+    }
+
+    /// The length to pass as the hidden Fortran length argument, without the NUL terminator.
+    pub fn len(&self) -> usize {
+        self.bytes.len() - 1
+    }
+}
+
+/// A buffer receiving a Fortran `CHARACTER` result from the kernel.
+///
+/// The kernel fills the buffer up to its length with blanks and doesn't terminate it with NUL.
+pub(crate) struct FortranBuffer {
+    bytes: Vec<u8>,
+}
+
+impl FortranBuffer {
+    /// Allocates a blank buffer of `len` bytes. Negative lengths reported by the kernel
+    /// result in an empty buffer.
+    pub fn new(len: i32) -> Self {
+        FortranBuffer {
+            bytes: vec![b' '; len.max(0) as usize],
+        }
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut c_char {
+        self.bytes.as_mut_ptr() as *mut c_char
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Reads the content up to the first NUL, if any, without the trailing blanks.
+    pub fn to_string_lossy(&self) -> String {
+        let content = match self.bytes.iter().position(|&b| b == 0) {
+            Some(end) => &self.bytes[..end],
+            None => &self.bytes[..],
+        };
+        let end = content
+            .iter()
+            .rposition(|&b| b != b' ')
+            .map_or(0, |last| last + 1);
+        String::from_utf8_lossy(&content[..end]).into_owned()
+    }
+}
+
+/// Truncates the string to at most `max_len` bytes at a UTF-8 character boundary.
+pub(crate) fn truncate_utf8(s: &str, max_len: usize) -> &str {
+    if s.len() <= max_len {
+        return s;
+    }
+    let mut end = max_len;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncates_at_a_character_boundary() {
+        assert_eq!(truncate_utf8("héllo", 10), "héllo");
+        assert_eq!(truncate_utf8("héllo", 3), "hé");
+        // `é` takes the bytes 1 and 2
+        assert_eq!(truncate_utf8("héllo", 2), "h");
+        assert_eq!(truncate_utf8("€", 2), "");
+        assert_eq!(FortranString::with_max_len("héllo", 2).len(), 1);
+    }
+
+    #[test]
+    fn replaces_interior_nul_characters() {
+        let mut string = FortranString::new("a\0b");
+        assert_eq!(string.len(), 3);
+        let bytes = unsafe { std::slice::from_raw_parts(string.as_mut_ptr() as *const u8, 4) };
+        assert_eq!(bytes, b"a b\0");
+    }
+
+    #[test]
+    fn reads_buffers_without_trailing_blanks() {
+        let mut buffer = FortranBuffer::new(8);
+        let text = b"abc  \0xy";
+        unsafe {
+            std::ptr::copy_nonoverlapping(text.as_ptr(), buffer.as_mut_ptr() as *mut u8, 8);
+        }
+        assert_eq!(buffer.to_string_lossy(), "abc");
+        assert_eq!(FortranBuffer::new(4).to_string_lossy(), "");
+        assert_eq!(FortranBuffer::new(-1).len(), 0);
+    }
+}
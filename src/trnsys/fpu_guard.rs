@@ -1,4 +1,8 @@
 #[cfg(target_os = "windows")]
+#[allow(
+    clippy::module_inception,
+    reason = "one `fpu_guard` module per platform, re-exported as `trnsys::FpuGuard`"
+)]
 pub(crate) mod fpu_guard {
     use std::os::raw::c_uint;
 
@@ -47,9 +51,13 @@ pub(crate) mod fpu_guard {
     }
 }
 
-// Empty implementation for non-Windows platforms (just in case)
+// Empty implementation for non-Windows platforms, which build against the mock kernel
 #[cfg(not(target_os = "windows"))]
-mod fpu_guard {
+#[allow(
+    clippy::module_inception,
+    reason = "one `fpu_guard` module per platform, re-exported as `trnsys::FpuGuard`"
+)]
+pub(crate) mod fpu_guard {
     pub struct FpuGuard;
     impl FpuGuard {
         pub fn new() -> Self {
@@ -0,0 +1,271 @@
+use crate::trnsys::error::TrnSysError;
+use crate::trnsys::CallPhase;
+use std::cell::Cell;
+use std::fmt::{Display, Formatter};
+
+macro_rules! index_type {
+    ($(#[$meta:meta])* $name:ident, $kind:literal) => {
+        $(#[$meta])*
+        ///
+        /// The index is **0-based**, the conversion to the 1-based Fortran index is done by the
+        /// kernel wrappers.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name(pub usize);
+
+        impl $name {
+            pub const KIND: &'static str = $kind;
+
+            /// Checks the index against the declared count and returns the 1-based index
+            /// expected by the kernel.
+            pub(crate) fn to_fortran(self, count: i32) -> Result<i32, TrnSysError> {
+                if self.0 < count.max(0) as usize {
+                    Ok(self.0 as i32 + 1)
+                } else {
+                    Err(TrnSysError::IndexOutOfRange {
+                        kind: Self::KIND,
+                        index: self.0,
+                        count,
+                    })
+                }
+            }
+        }
+
+        impl From<usize> for $name {
+            fn from(index: usize) -> Self {
+                $name(index)
+            }
+        }
+
+        impl Display for $name {
+            /// Displays the index as it is numbered in the deck, i.e. 1-based.
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{} {}", Self::KIND, self.0 + 1)
+            }
+        }
+    };
+}
+
+index_type!(
+    /// Index of an input of the current unit.
+    InputIndex,
+    "input"
+);
+index_type!(
+    /// Index of an output of the current unit.
+    OutputIndex,
+    "output"
+);
+index_type!(
+    /// Index of a parameter of the current unit.
+    ParamIndex,
+    "parameter"
+);
+index_type!(
+    /// Index of a static or dynamic storage slot of the current unit.
+    StorageIndex,
+    "storage"
+);
+
+/// The counts the Type declared to the kernel at the first call of the simulation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeclaredCounts {
+    pub inputs: i32,
+    pub outputs: i32,
+    pub params: i32,
+    pub static_storage: i32,
+    pub dynamic_storage: i32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ActiveCall {
+    phase: CallPhase,
+    counts: DeclaredCounts,
+}
+
+thread_local! {
+    static ACTIVE_CALL: Cell<Option<ActiveCall>> = const { Cell::new(None) };
+}
+
+/// Marks the duration of a kernel call on the current thread.
+///
+/// While the scope is alive, the kernel wrappers check their indices against the declared
+/// counts and, in debug builds, whether they may be called in the current phase.
+pub(crate) struct CallScope {
+    previous: Option<ActiveCall>,
+}
+
+impl CallScope {
+    pub fn enter(phase: CallPhase, counts: DeclaredCounts) -> Self {
+        let previous = ACTIVE_CALL.replace(Some(ActiveCall { phase, counts }));
+        CallScope { previous }
+    }
+
+    /// Updates the counts of the current call, e.g. after they were read at the first call.
+    pub fn declare_counts(&self, counts: DeclaredCounts) {
+        ACTIVE_CALL.with(|call| {
+            if let Some(mut active) = call.get() {
+                active.counts = counts;
+                call.set(Some(active));
+            }
+        });
+    }
+}
+
+impl Drop for CallScope {
+    fn drop(&mut self) {
+        ACTIVE_CALL.set(self.previous);
+    }
+}
+
+/// Returns the counts declared by the unit which is currently called.
+pub(crate) fn declared_counts(function: &'static str) -> Result<DeclaredCounts, TrnSysError> {
+    ACTIVE_CALL
+        .get()
+        .map(|call| call.counts)
+        .ok_or(TrnSysError::NoActiveCall { function })
+}
+
+/// All the phases after the first call of the simulation.
+pub(crate) const RUNNING_PHASES: &[CallPhase] = &[
+    CallPhase::StartTime,
+    CallPhase::Iteration,
+    CallPhase::RereadParameters,
+    CallPhase::EndOfTimestep,
+    CallPhase::LastCall,
+];
+
+/// The phases in which the parameters can be read, which includes the first call.
+pub(crate) const PARAMETER_PHASES: &[CallPhase] = &[
+    CallPhase::FirstCall,
+    CallPhase::StartTime,
+    CallPhase::Iteration,
+    CallPhase::RereadParameters,
+    CallPhase::EndOfTimestep,
+    CallPhase::LastCall,
+];
+
+/// The phases in which the outputs of the Type are computed.
+pub(crate) const OUTPUT_PHASES: &[CallPhase] = &[
+    CallPhase::StartTime,
+    CallPhase::Iteration,
+    CallPhase::RereadParameters,
+];
+
+/// Checks whether `function` may be called in the phase of the current call.
+///
+/// The check is only performed in debug builds.
+pub(crate) fn check_phase(
+    function: &'static str,
+    allowed: &[CallPhase],
+) -> Result<(), TrnSysError> {
+    #[cfg(debug_assertions)]
+    {
+        let call = ACTIVE_CALL
+            .get()
+            .ok_or(TrnSysError::NoActiveCall { function })?;
+        if !allowed.contains(&call.phase) {
+            return Err(TrnSysError::IllegalPhase {
+                function,
+                phase: call.phase,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COUNTS: DeclaredCounts = DeclaredCounts {
+        inputs: 2,
+        outputs: 1,
+        params: 3,
+        static_storage: 0,
+        dynamic_storage: 0,
+    };
+
+    #[test]
+    fn converts_to_1_based_indices() {
+        assert_eq!(InputIndex(0).to_fortran(2).unwrap(), 1);
+        assert_eq!(InputIndex(1).to_fortran(2).unwrap(), 2);
+        assert_eq!(ParamIndex(2).to_string(), "parameter 3");
+    }
+
+    #[test]
+    fn rejects_indices_beyond_the_declared_counts() {
+        let _scope = CallScope::enter(CallPhase::Iteration, COUNTS);
+        let counts = declared_counts("get_input_value").unwrap();
+        assert!(matches!(
+            InputIndex(2).to_fortran(counts.inputs),
+            Err(TrnSysError::IndexOutOfRange {
+                kind: "input",
+                index: 2,
+                count: 2
+            })
+        ));
+        assert!(OutputIndex(0).to_fortran(counts.outputs).is_ok());
+        assert!(StorageIndex(0).to_fortran(counts.static_storage).is_err());
+        assert!(StorageIndex(0).to_fortran(-1).is_err());
+        // the wrappers fail before calling the kernel
+        assert!(matches!(
+            crate::trnsys::get_input_value(InputIndex(2)),
+            Err(TrnSysError::IndexOutOfRange { kind: "input", .. })
+        ));
+    }
+
+    #[test]
+    fn counts_are_only_known_during_a_call() {
+        assert!(matches!(
+            declared_counts("get_input_value"),
+            Err(TrnSysError::NoActiveCall {
+                function: "get_input_value"
+            })
+        ));
+        {
+            let scope = CallScope::enter(CallPhase::FirstCall, DeclaredCounts::default());
+            scope.declare_counts(COUNTS);
+            assert_eq!(declared_counts("get_input_value").unwrap(), COUNTS);
+            {
+                let _nested = CallScope::enter(CallPhase::Iteration, DeclaredCounts::default());
+                assert_eq!(declared_counts("get_input_value").unwrap().inputs, 0);
+            }
+            assert_eq!(declared_counts("get_input_value").unwrap(), COUNTS);
+        }
+        assert!(declared_counts("get_input_value").is_err());
+        assert!(matches!(
+            crate::trnsys::get_input_value(InputIndex(0)),
+            Err(TrnSysError::NoActiveCall { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn checks_the_phase_of_the_call() {
+        assert!(matches!(
+            check_phase("set_output_value", OUTPUT_PHASES),
+            Err(TrnSysError::NoActiveCall {
+                function: "set_output_value"
+            })
+        ));
+        let scope = CallScope::enter(CallPhase::FirstCall, COUNTS);
+        assert!(check_phase("get_parameter_value", PARAMETER_PHASES).is_ok());
+        assert!(matches!(
+            check_phase("get_input_value", RUNNING_PHASES),
+            Err(TrnSysError::IllegalPhase {
+                function: "get_input_value",
+                phase: CallPhase::FirstCall
+            })
+        ));
+        drop(scope);
+        let _scope = CallScope::enter(CallPhase::EndOfTimestep, COUNTS);
+        assert!(check_phase("get_input_value", RUNNING_PHASES).is_ok());
+        assert!(matches!(
+            check_phase("set_output_value", OUTPUT_PHASES),
+            Err(TrnSysError::IllegalPhase {
+                phase: CallPhase::EndOfTimestep,
+                ..
+            })
+        ));
+    }
+}
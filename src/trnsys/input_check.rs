@@ -0,0 +1,199 @@
+use crate::trnsys::error::{InputError, TrnSysError};
+use crate::trnsys::param::TrnSysValue;
+use crate::trnsys::{found_bad_input, CallContext, CallPhase, InputIndex, Severity};
+use std::ops::RangeInclusive;
+use tracing::info;
+
+/// What happens when an input is out of its declared range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputPolicy {
+    /// Report a warning the first time the input is out of range only.
+    WarnOnce,
+    /// Report a warning at every time step the input is out of range, up to
+    /// [`InputChecker::max_warnings`] warnings per input.
+    WarnEachTime,
+    /// Stop the simulation with a fatal error.
+    Fatal,
+}
+
+/// When the declared input ranges are checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CheckTiming {
+    /// After the inputs are read, at every iteration.
+    EveryIteration,
+    /// At the end of each time step, when the inputs have converged. Inputs which are only out of
+    /// range in intermediate iterations are not reported.
+    #[default]
+    ConvergedTimestep,
+}
+
+#[derive(Debug, Clone)]
+struct InputCheck {
+    range: RangeInclusive<f64>,
+    policy: InputPolicy,
+    warnings: u32,
+    last_warning_time: Option<f64>,
+}
+
+/// Checks the inputs against their declared physical ranges and reports the violations to the
+/// kernel through `found_bad_input`.
+#[derive(Debug, Clone)]
+pub struct InputChecker {
+    pub timing: CheckTiming,
+    /// The maximum number of warnings reported for one input with [`InputPolicy::WarnEachTime`].
+    pub max_warnings: u32,
+    checks: Vec<Option<InputCheck>>,
+}
+
+impl Default for InputChecker {
+    fn default() -> Self {
+        InputChecker {
+            timing: CheckTiming::default(),
+            max_warnings: 20,
+            checks: vec![],
+        }
+    }
+}
+
+impl InputChecker {
+    /// Declares the physical range of an input and what to do when it is violated.
+    pub fn set_range(&mut self, i: InputIndex, range: RangeInclusive<f64>, policy: InputPolicy) {
+        if self.checks.len() <= i.0 {
+            self.checks.resize(i.0 + 1, None);
+        }
+        self.checks[i.0] = Some(InputCheck {
+            range,
+            policy,
+            warnings: 0,
+            last_warning_time: None,
+        });
+    }
+
+    /// Checks the inputs if the declared timing applies to the phase of the call.
+    ///
+    /// Warnings are reported directly, a fatal violation is returned as an
+    /// [`InputError::BadInput`] and reported by its error handler.
+    pub fn check(&mut self, ctx: &CallContext, inputs: &[TrnSysValue]) -> Result<(), TrnSysError> {
+        let due = match self.timing {
+            CheckTiming::EveryIteration => ctx.phase.is_iterative(),
+            CheckTiming::ConvergedTimestep => ctx.phase == CallPhase::EndOfTimestep,
+        };
+        if !due {
+            return Ok(());
+        }
+
+        for (i, (check, input)) in self.checks.iter_mut().zip(inputs).enumerate() {
+            let Some(check) = check else { continue };
+            let value = input.value;
+            if check.range.contains(&value) {
+                continue;
+            }
+
+            let index = InputIndex(i);
+            let message = format!(
+                "{} = {} is out of range {:?} at t={}",
+                index, value, check.range, ctx.time
+            );
+            let report = match check.policy {
+                InputPolicy::Fatal => {
+                    return Err(InputError::BadInput { index, message }.into());
+                }
+                InputPolicy::WarnOnce => check.warnings == 0,
+                // at most one warning per time step
+                InputPolicy::WarnEachTime => {
+                    check.warnings < self.max_warnings && check.last_warning_time != Some(ctx.time)
+                }
+            };
+            if !report {
+                continue;
+            }
+
+            check.warnings += 1;
+            check.last_warning_time = Some(ctx.time);
+            found_bad_input(index, Severity::Warning, &message)?;
+            if check.policy == InputPolicy::WarnEachTime && check.warnings == self.max_warnings {
+                info!("Further warnings for {} are suppressed", index);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(
+        checker: &mut InputChecker,
+        phase: CallPhase,
+        time: f64,
+        value: f64,
+    ) -> Result<(), TrnSysError> {
+        let ctx = CallContext::new(phase, time, 1., 1, 1, 256);
+        checker.check(&ctx, &[TrnSysValue::new(value)])
+    }
+
+    fn checker(policy: InputPolicy) -> InputChecker {
+        let mut checker = InputChecker::default();
+        checker.set_range(InputIndex(0), 0.0..=100., policy);
+        checker
+    }
+
+    #[test]
+    fn fatal_violations_are_returned() {
+        let mut checker = checker(InputPolicy::Fatal);
+        assert!(check(&mut checker, CallPhase::EndOfTimestep, 1., 50.).is_ok());
+        assert!(matches!(
+            check(&mut checker, CallPhase::EndOfTimestep, 1., f64::NAN),
+            Err(TrnSysError::InputError(InputError::BadInput {
+                index: InputIndex(0),
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn checks_at_the_declared_timing() {
+        let mut checker = checker(InputPolicy::Fatal);
+        // intermediate iterations are not checked by default
+        assert!(check(&mut checker, CallPhase::Iteration, 1., 150.).is_ok());
+        checker.timing = CheckTiming::EveryIteration;
+        assert!(check(&mut checker, CallPhase::Iteration, 1., 150.).is_err());
+        assert!(check(&mut checker, CallPhase::EndOfTimestep, 1., 150.).is_ok());
+    }
+
+    /// Runs the checks of the time steps `times` in a call scope, and returns the warnings the
+    /// mock kernel received.
+    #[cfg(mock_kernel)]
+    fn warnings(checker: &mut InputChecker, times: &[f64]) -> usize {
+        use crate::trnsys::index::{CallScope, DeclaredCounts};
+        use crate::trnsys::mock_kernel::with_mock_kernel;
+
+        let counts = DeclaredCounts {
+            inputs: 1,
+            ..DeclaredCounts::default()
+        };
+        let _scope = CallScope::enter(CallPhase::EndOfTimestep, counts);
+        with_mock_kernel(|kernel| kernel.bad_inputs.clear());
+        for &time in times {
+            check(checker, CallPhase::EndOfTimestep, time, 150.).unwrap();
+        }
+        with_mock_kernel(|kernel| kernel.bad_inputs.len())
+    }
+
+    #[test]
+    #[cfg(mock_kernel)]
+    fn warns_once() {
+        let mut checker = checker(InputPolicy::WarnOnce);
+        assert_eq!(warnings(&mut checker, &[1., 2., 3.]), 1);
+    }
+
+    #[test]
+    #[cfg(mock_kernel)]
+    fn warns_once_per_time_step_up_to_the_limit() {
+        let mut checker = checker(InputPolicy::WarnEachTime);
+        checker.max_warnings = 3;
+        assert_eq!(warnings(&mut checker, &[1., 1., 2.]), 2);
+        assert_eq!(warnings(&mut checker, &[3., 4., 5.]), 1);
+    }
+}
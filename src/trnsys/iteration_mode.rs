@@ -7,12 +7,13 @@
 /// After that, Integrators and Printers are called. \
 /// Finally, the after-convergence-and-printers types are called.
 #[repr(i32)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum IterationMode {
     /// The Type's outputs only depend upon its input values and not explicitly upon time.
     StaticMode = 0,
     /// The Type's outputs depend upon the passage of time and the Type must therefore be called
     /// at least once every time step even if the values of inputs do not change
+    #[default]
     DynamicMode = 1,
     /// The Type should be called after all other components have converged and before the
     /// integrators and printers.
@@ -26,12 +27,6 @@ pub enum IterationMode {
     AfterConvergenceAndPrinters = 2,
 }
 
-impl Default for IterationMode {
-    fn default() -> Self {
-        IterationMode::DynamicMode
-    }
-}
-
 impl From<IterationMode> for i32 {
     fn from(mode: IterationMode) -> Self {
         mode as i32
@@ -0,0 +1,230 @@
+use crate::trnsys::call_context::CallPhase;
+use crate::trnsys::logging::CallFields;
+use serde_json::{json, Map, Value};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// The name of the JSON Lines file written next to the log file `name`.
+pub(crate) fn json_lines_name(name: &str) -> String {
+    format!("{}.jsonl", name.strip_suffix(".log").unwrap_or(name))
+}
+
+pub(crate) fn json_lines_path(log_file: &Path) -> PathBuf {
+    let name = log_file
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    log_file.with_file_name(json_lines_name(&name))
+}
+
+/// Collects the fields of an event as JSON values.
+struct JsonFields {
+    message: Option<String>,
+    fields: Map<String, Value>,
+}
+
+impl JsonFields {
+    fn record(&mut self, field: &Field, value: Value) {
+        if field.name() == "message" {
+            self.message = value.as_str().map(str::to_string);
+        } else {
+            self.fields.insert(field.name().to_string(), value);
+        }
+    }
+}
+
+impl Visit for JsonFields {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        // NaN and infinities are written as null
+        self.record(field, Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record(field, Value::from(format!("{:?}", value)));
+    }
+}
+
+/// Writes every event as one JSON object per line, for analysis after the run:
+///
+/// ```json
+/// {"wall_time":1767225600.5,"level":"WARN","target":"trnsys_rust_template::trnsys_type","unit":12,"type_number":256,"phase":"Iteration","time":1.5,"iteration":3,"message":"...","fields":{"flow":0.01}}
+/// ```
+///
+/// The call fields are `null` for events outside of a kernel call.
+pub(crate) struct JsonLinesLayer<W> {
+    make_writer: W,
+}
+
+impl<W> JsonLinesLayer<W>
+where
+    W: for<'a> MakeWriter<'a> + 'static,
+{
+    pub fn new(make_writer: W) -> Self {
+        JsonLinesLayer { make_writer }
+    }
+}
+
+impl<S, W> Layer<S> for JsonLinesLayer<W>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'a> MakeWriter<'a> + 'static,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut fields = JsonFields {
+            message: None,
+            fields: Map::new(),
+        };
+        event.record(&mut fields);
+        let call = CallFields::of_event(event, &ctx);
+        let wall_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or_default();
+
+        let line = json!({
+            "wall_time": wall_time,
+            "level": metadata.level().as_str(),
+            "target": metadata.target(),
+            "unit": call.as_ref().map(|call| call.unit),
+            "type_number": call.as_ref().map(|call| call.type_number),
+            "phase": call.as_ref().and_then(|call| call.phase).map(CallPhase::name),
+            "time": call.as_ref().map(|call| call.time),
+            "iteration": call.as_ref().map(|call| call.iteration),
+            "message": fields.message,
+            "fields": fields.fields,
+        });
+        let mut buffer = line.to_string();
+        buffer.push('\n');
+        // a failed write cannot be logged without recursing into the logging
+        let _ = self.make_writer.make_writer().write_all(buffer.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trnsys::call_context::{CallContext, CALL_SPAN_NAME};
+    use std::sync::{Arc, Mutex};
+    use tracing::span::{Attributes, Id};
+    use tracing::warn;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::Registry;
+
+    /// Keeps the fields of call spans, like the list file layer does.
+    struct CallSpans;
+
+    impl<S> Layer<S> for CallSpans
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            if attrs.metadata().name() == CALL_SPAN_NAME {
+                let mut fields = CallFields::default();
+                attrs.record(&mut fields);
+                ctx.span(id).unwrap().extensions_mut().insert(fields);
+            }
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Lines(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Lines {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// The JSON objects of the lines written for the events.
+    fn json_lines(events: impl FnOnce()) -> Vec<Value> {
+        let lines = Lines::default();
+        let writer = lines.clone();
+        let subscriber = Registry::default()
+            .with(CallSpans)
+            .with(JsonLinesLayer::new(move || writer.clone()));
+        tracing::subscriber::with_default(subscriber, events);
+
+        let lines = lines.0.lock().unwrap();
+        std::str::from_utf8(&lines)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn names_the_file_after_the_log_file() {
+        assert_eq!(json_lines_name("deck_42.log"), "deck_42.jsonl");
+        assert_eq!(json_lines_name("deck_42.txt"), "deck_42.txt.jsonl");
+        assert_eq!(
+            json_lines_path(Path::new("logs/deck_42.log")),
+            Path::new("logs/deck_42.jsonl")
+        );
+    }
+
+    #[test]
+    fn writes_the_call_context_of_the_event() {
+        let ctx = CallContext::new(CallPhase::Iteration, 1.5, 0.25, 3, 12, 256);
+        let lines = json_lines(|| {
+            ctx.span()
+                .in_scope(|| warn!(flow = 0.01, pump = "P1", "Flow rate low"));
+        });
+        assert_eq!(lines.len(), 1);
+
+        let mut line = lines[0].clone();
+        assert!(line["wall_time"].as_f64().unwrap() > 0.);
+        line.as_object_mut().unwrap().remove("wall_time");
+        assert_eq!(
+            line,
+            json!({
+                "level": "WARN",
+                "target": module_path!(),
+                "unit": 12,
+                "type_number": 256,
+                "phase": "Iteration",
+                "time": 1.5,
+                "iteration": 3,
+                "message": "Flow rate low",
+                "fields": {"flow": 0.01, "pump": "P1"},
+            })
+        );
+    }
+
+    #[test]
+    fn writes_null_call_fields_outside_of_a_call() {
+        let lines = json_lines(|| warn!(nan = f64::NAN, "Outside"));
+        assert_eq!(lines.len(), 1);
+        for field in ["unit", "type_number", "phase", "time", "iteration"] {
+            assert!(lines[0][field].is_null(), "{}", field);
+        }
+        assert_eq!(lines[0]["fields"], json!({"nan": null}));
+    }
+}
@@ -0,0 +1,228 @@
+use std::ffi::CStr;
+use std::sync::OnceLock;
+
+/// The kernel module the Type is linked against.
+#[cfg(windows)]
+const KERNEL_MODULE: &CStr = c"TRNDll64.dll";
+
+/// A function only TRNSYS 18 exports, which tells the kernels apart.
+const INIT_REPORT_INTEGRAL: &CStr =
+    match CStr::from_bytes_with_nul(concat!(kernel_symbol!(INITREPORTINTEGRAL), "\0").as_bytes()) {
+        Ok(name) => name,
+        Err(_) => panic!("symbol without NUL"),
+    };
+
+/// A TRNSYS version a Type can be signed with, see `trnsys_version` in `[package.metadata.trnsys]`.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TrnSysVersion {
+    V17 = 17,
+    V18 = 18,
+}
+
+impl TrnSysVersion {
+    /// The version the Type is written for, from the metadata in `Cargo.toml`.
+    pub fn of_type() -> Self {
+        TrnSysVersion::try_from(crate::TRNSYS_VERSION).expect("checked by the build script")
+    }
+
+    pub fn number(self) -> i32 {
+        self as i32
+    }
+}
+
+impl TryFrom<i32> for TrnSysVersion {
+    type Error = i32;
+
+    fn try_from(version: i32) -> Result<Self, Self::Error> {
+        match version {
+            17 => Ok(TrnSysVersion::V17),
+            18 => Ok(TrnSysVersion::V18),
+            _ => Err(version),
+        }
+    }
+}
+
+/// Kernel functions and behaviors which not every supported kernel has.
+///
+/// Only the simulation summary report is told apart so far, the one capability of TRNSYS 18 the
+/// Type uses which TRNSYS 17 lacks. A new feature names a function only the kernels with it
+/// export, see [`KernelFeature::function`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KernelFeature {
+    /// The simulation summary report: `is_included_in_ssr`, `set_number_of_report_variables`,
+    /// `init_report_*` and `update_report_*`.
+    SimulationSummaryReport,
+}
+
+impl KernelFeature {
+    pub fn introduced_in(self) -> TrnSysVersion {
+        match self {
+            KernelFeature::SimulationSummaryReport => TrnSysVersion::V18,
+        }
+    }
+
+    /// A function the kernel exports if it has the feature.
+    fn function(self) -> &'static CStr {
+        match self {
+            KernelFeature::SimulationSummaryReport => INIT_REPORT_INTEGRAL,
+        }
+    }
+}
+
+/// The kernel which loaded the DLL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Kernel {
+    pub version: TrnSysVersion,
+    /// Whether the version was detected from the functions the kernel exports. Otherwise, it is
+    /// assumed to be the version the Type is written for.
+    pub detected: bool,
+}
+
+impl Kernel {
+    /// Whether the kernel has the feature. A kernel whose version is assumed only supports the
+    /// features whose functions can be looked up.
+    pub fn supports(&self, feature: KernelFeature) -> bool {
+        self.version >= feature.introduced_in()
+            && (self.detected || kernel_function_address(feature.function()).is_some())
+    }
+
+    /// The version the Type signs with: the version it is written for, or the version of the
+    /// kernel if it is older.
+    pub fn signing_version(&self) -> TrnSysVersion {
+        TrnSysVersion::of_type().min(self.version)
+    }
+}
+
+/// The kernel which loaded the DLL, detected at the first call.
+///
+/// TRNSYS 18 exports the functions of the simulation summary report, TRNSYS 17 doesn't.
+pub fn kernel() -> &'static Kernel {
+    static KERNEL: OnceLock<Kernel> = OnceLock::new();
+    KERNEL.get_or_init(|| match kernel_module() {
+        Some(_) => Kernel {
+            version: if kernel_function_address(INIT_REPORT_INTEGRAL).is_some() {
+                TrnSysVersion::V18
+            } else {
+                TrnSysVersion::V17
+            },
+            detected: true,
+        },
+        None => Kernel {
+            version: TrnSysVersion::of_type(),
+            detected: false,
+        },
+    })
+}
+
+/// Looks up a function of the kernel which not every version exports. Such functions are not
+/// imported by the DLL, so that it loads in every kernel.
+///
+/// # Safety
+///
+/// `F` must be the `unsafe extern "C" fn` type of the function, as declared in `ext_c`.
+pub(crate) unsafe fn kernel_function<F: Copy>(name: &CStr) -> Option<F> {
+    assert_eq!(size_of::<F>(), size_of::<usize>());
+    kernel_function_address(name).map(|address| unsafe { std::mem::transmute_copy(&address) })
+}
+
+#[cfg(windows)]
+mod win32 {
+    use std::ffi::c_void;
+    use std::os::raw::c_char;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        pub fn GetModuleHandleA(module_name: *const c_char) -> *mut c_void;
+        pub fn GetProcAddress(module: *mut c_void, proc_name: *const c_char) -> *mut c_void;
+    }
+}
+
+#[cfg(windows)]
+fn kernel_module() -> Option<usize> {
+    let module = unsafe { win32::GetModuleHandleA(KERNEL_MODULE.as_ptr()) };
+    (!module.is_null()).then_some(module as usize)
+}
+
+#[cfg(not(windows))]
+fn kernel_module() -> Option<usize> {
+    None
+}
+
+#[cfg(windows)]
+fn kernel_function_address(name: &CStr) -> Option<usize> {
+    let module = kernel_module()?;
+    let address = unsafe { win32::GetProcAddress(module as *mut _, name.as_ptr()) };
+    (!address.is_null()).then_some(address as usize)
+}
+
+#[cfg(not(windows))]
+fn kernel_function_address(name: &CStr) -> Option<usize> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_supported_versions() {
+        assert_eq!(TrnSysVersion::try_from(17), Ok(TrnSysVersion::V17));
+        assert_eq!(TrnSysVersion::try_from(18), Ok(TrnSysVersion::V18));
+        assert_eq!(TrnSysVersion::try_from(16), Err(16));
+        assert_eq!(TrnSysVersion::try_from(19), Err(19));
+        assert_eq!(TrnSysVersion::V17.number(), 17);
+        assert!(TrnSysVersion::V17 < TrnSysVersion::V18);
+    }
+
+    #[test]
+    fn the_type_is_written_for_the_version_of_the_metadata() {
+        let manifest: toml::Value = toml::from_str(include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/Cargo.toml"
+        )))
+        .unwrap();
+        let version = manifest["package"]["metadata"]["trnsys"]
+            .get("trnsys_version")
+            .map_or(18, |version| version.as_integer().unwrap());
+        assert_eq!(i64::from(TrnSysVersion::of_type().number()), version);
+    }
+
+    #[test]
+    fn signs_with_the_older_of_the_type_and_the_kernel() {
+        let detected = |version| Kernel {
+            version,
+            detected: true,
+        };
+        // no Type is written for a version older than TRNSYS 17 or newer than TRNSYS 18
+        assert_eq!(
+            detected(TrnSysVersion::V17).signing_version(),
+            TrnSysVersion::V17
+        );
+        assert_eq!(
+            detected(TrnSysVersion::V18).signing_version(),
+            TrnSysVersion::of_type()
+        );
+    }
+
+    #[test]
+    fn supports_the_features_of_the_detected_version() {
+        let feature = KernelFeature::SimulationSummaryReport;
+        let detected = |version| Kernel {
+            version,
+            detected: true,
+        };
+        assert!(!detected(TrnSysVersion::V17).supports(feature));
+        assert!(detected(TrnSysVersion::V18).supports(feature));
+
+        // an assumed version only supports the functions which can be looked up
+        let assumed = Kernel {
+            version: TrnSysVersion::V18,
+            detected: false,
+        };
+        assert_eq!(
+            assumed.supports(feature),
+            kernel_function_address(feature.function()).is_some()
+        );
+    }
+}
@@ -0,0 +1,295 @@
+use crate::trnsys::log_config::ListFileLimits;
+use crate::trnsys::{log_message, Severity};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use tracing::callsite::Identifier;
+use tracing::{Level, Metadata};
+
+/// The number of callsites listed in the summary of the suppressed messages.
+const SUMMARY_CALLSITES: usize = 10;
+
+#[derive(Debug)]
+struct CallsiteCount {
+    unit: i32,
+    location: String,
+    in_timestep: u32,
+    total: u32,
+    suppressed: u32,
+}
+
+/// Counts the messages forwarded to the list file, to suppress the ones over the limits.
+#[derive(Debug, Default)]
+struct ListFileLimiter {
+    limits: ListFileLimits,
+    time: Option<f64>,
+    in_timestep: u32,
+    total: u32,
+    suppressed: u32,
+    callsites: HashMap<(i32, Identifier), CallsiteCount>,
+}
+
+impl ListFileLimiter {
+    fn new(limits: ListFileLimits) -> Self {
+        ListFileLimiter {
+            limits,
+            ..ListFileLimiter::default()
+        }
+    }
+
+    /// Counts a message of the callsite, and returns whether it is within the limits.
+    fn allow(&mut self, metadata: &Metadata<'_>, unit: i32, time: f64) -> bool {
+        let limits = &self.limits;
+        if self.time != Some(time) {
+            self.time = Some(time);
+            self.in_timestep = 0;
+            self.callsites.values_mut().for_each(|c| c.in_timestep = 0);
+        }
+
+        let callsite = self
+            .callsites
+            .entry((unit, metadata.callsite()))
+            .or_insert_with(|| CallsiteCount {
+                unit,
+                location: format!(
+                    "{}:{}",
+                    metadata.file().unwrap_or(metadata.target()),
+                    metadata.line().unwrap_or_default()
+                ),
+                in_timestep: 0,
+                total: 0,
+                suppressed: 0,
+            });
+
+        let allowed = callsite.in_timestep < limits.per_callsite_per_timestep
+            && callsite.total < limits.per_callsite
+            && self.in_timestep < limits.per_timestep
+            && self.total < limits.total;
+        if allowed {
+            callsite.in_timestep += 1;
+            callsite.total += 1;
+            self.in_timestep += 1;
+            self.total += 1;
+        } else {
+            callsite.suppressed += 1;
+            self.suppressed += 1;
+        }
+        allowed
+    }
+
+    /// The summary of the suppressed messages: their number, then the callsites that suppressed
+    /// the most.
+    fn summary(&self) -> Vec<String> {
+        if self.suppressed == 0 {
+            return vec![];
+        }
+        let mut summary = vec![format!(
+            "{} messages were not written to the list file, see the log file",
+            self.suppressed
+        )];
+
+        let mut callsites: Vec<_> = self
+            .callsites
+            .values()
+            .filter(|c| c.suppressed > 0)
+            .collect();
+        callsites.sort_by_key(|c| Reverse(c.suppressed));
+        summary.extend(callsites.iter().take(SUMMARY_CALLSITES).map(|callsite| {
+            format!(
+                "Unit {} at {}: {} messages suppressed",
+                callsite.unit, callsite.location, callsite.suppressed
+            )
+        }));
+        summary
+    }
+}
+
+static LIMITER: LazyLock<Mutex<ListFileLimiter>> =
+    LazyLock::new(|| Mutex::new(ListFileLimiter::default()));
+
+/// Sets the limits and resets the counters.
+pub(crate) fn configure_list_file_limits(limits: ListFileLimits) {
+    *LIMITER.lock().unwrap_or_else(|e| e.into_inner()) = ListFileLimiter::new(limits);
+}
+
+/// Serializes the tests that use the global limiter.
+#[cfg(test)]
+static TEST_LIMITER: Mutex<()> = Mutex::new(());
+
+/// Resets the global limiter to `limits` for a test, which has the limiter to itself as long as it
+/// holds the returned guard.
+#[cfg(test)]
+pub(crate) fn reset_list_file_limiter(
+    limits: ListFileLimits,
+) -> std::sync::MutexGuard<'static, ()> {
+    let guard = TEST_LIMITER.lock().unwrap_or_else(|e| e.into_inner());
+    configure_list_file_limits(limits);
+    guard
+}
+
+/// Whether a message of the callsite may be forwarded to the list file.
+///
+/// Errors are always forwarded, since they stop the simulation. Other messages are collapsed per
+/// unit and callsite, and capped per time step and per simulation.
+pub(crate) fn allow_in_list_file(metadata: &Metadata<'_>, unit: i32, time: f64) -> bool {
+    if *metadata.level() == Level::ERROR {
+        return true;
+    }
+    LIMITER
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .allow(metadata, unit, time)
+}
+
+/// Reports the number of suppressed messages to the list file, called at the last call as messages
+/// of the unit called last.
+pub(crate) fn log_suppressed_messages(unit: i32, type_number: i32) {
+    let summary = LIMITER.lock().unwrap_or_else(|e| e.into_inner()).summary();
+    for message in summary {
+        log_message(Severity::Notice, -1, unit, type_number, &message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tracing::Event;
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::{Layer, Registry};
+
+    /// Collects the metadata of the events.
+    struct Callsites(Arc<Mutex<Vec<&'static Metadata<'static>>>>);
+
+    impl<S: tracing::Subscriber> Layer<S> for Callsites {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            self.0.lock().unwrap().push(event.metadata());
+        }
+    }
+
+    /// The metadata of two warnings and an error, each at its own callsite.
+    fn callsites() -> [&'static Metadata<'static>; 3] {
+        let callsites = Arc::new(Mutex::new(vec![]));
+        let subscriber = Registry::default().with(Callsites(callsites.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!("first");
+            tracing::warn!("second");
+            tracing::error!("error");
+        });
+        let callsites = callsites.lock().unwrap();
+        [callsites[0], callsites[1], callsites[2]]
+    }
+
+    fn unlimited() -> ListFileLimits {
+        ListFileLimits {
+            per_callsite_per_timestep: u32::MAX,
+            per_callsite: u32::MAX,
+            per_timestep: u32::MAX,
+            total: u32::MAX,
+        }
+    }
+
+    #[test]
+    fn limits_each_callsite_of_each_unit_per_timestep() {
+        let [first, second, _] = callsites();
+        let mut limiter = ListFileLimiter::new(ListFileLimits {
+            per_callsite_per_timestep: 1,
+            ..unlimited()
+        });
+
+        assert!(limiter.allow(first, 1, 0.));
+        assert!(!limiter.allow(first, 1, 0.));
+        assert!(limiter.allow(first, 2, 0.));
+        assert!(limiter.allow(second, 1, 0.));
+        assert!(limiter.allow(first, 1, 1.));
+        assert!(!limiter.allow(first, 1, 1.));
+    }
+
+    #[test]
+    fn limits_each_callsite_of_each_unit_per_simulation() {
+        let [first, second, _] = callsites();
+        let mut limiter = ListFileLimiter::new(ListFileLimits {
+            per_callsite: 2,
+            ..unlimited()
+        });
+
+        assert!(limiter.allow(first, 1, 0.));
+        assert!(limiter.allow(first, 1, 1.));
+        assert!(!limiter.allow(first, 1, 2.));
+        assert!(limiter.allow(first, 2, 2.));
+        assert!(limiter.allow(second, 1, 2.));
+    }
+
+    #[test]
+    fn limits_all_the_units_per_timestep_and_per_simulation() {
+        let [first, second, _] = callsites();
+        let mut limiter = ListFileLimiter::new(ListFileLimits {
+            per_timestep: 2,
+            total: 3,
+            ..unlimited()
+        });
+
+        assert!(limiter.allow(first, 1, 0.));
+        assert!(limiter.allow(second, 2, 0.));
+        assert!(!limiter.allow(first, 3, 0.));
+        assert!(limiter.allow(first, 1, 1.));
+        assert!(!limiter.allow(second, 2, 1.));
+        assert!(!limiter.allow(first, 1, 2.));
+    }
+
+    #[test]
+    fn summarizes_the_suppressed_messages() {
+        let [first, second, _] = callsites();
+        let mut limiter = ListFileLimiter::new(ListFileLimits {
+            per_callsite_per_timestep: 1,
+            ..unlimited()
+        });
+        assert!(limiter.summary().is_empty());
+
+        for _ in 0..3 {
+            limiter.allow(first, 1, 0.);
+        }
+        for _ in 0..4 {
+            limiter.allow(second, 2, 0.);
+        }
+
+        let summary = limiter.summary();
+        assert_eq!(summary.len(), 3);
+        assert_eq!(
+            summary[0],
+            "5 messages were not written to the list file, see the log file"
+        );
+        assert_eq!(
+            summary[1],
+            format!(
+                "Unit 2 at {}:{}: 3 messages suppressed",
+                file!(),
+                second.line().unwrap()
+            )
+        );
+        assert_eq!(
+            summary[2],
+            format!(
+                "Unit 1 at {}:{}: 2 messages suppressed",
+                file!(),
+                first.line().unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn errors_always_pass() {
+        let [warning, _, error] = callsites();
+        let _limiter = reset_list_file_limiter(ListFileLimits {
+            per_callsite_per_timestep: 0,
+            per_callsite: 0,
+            per_timestep: 0,
+            total: 0,
+        });
+
+        assert!(!allow_in_list_file(warning, 1, 0.));
+        for _ in 0..3 {
+            assert!(allow_in_list_file(error, 1, 0.));
+        }
+    }
+}
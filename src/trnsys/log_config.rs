@@ -0,0 +1,414 @@
+use crate::trnsys::{
+    get_deck_filename, get_label, get_number_of_labels, get_trnsys_input_file_dir,
+};
+use crate::TYPE_NUMBER;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tracing::Level;
+
+/// The name of the logging config file, looked up in the directory of the deck.
+pub const LOG_CONFIG_FILE: &str = "trnsys_log.toml";
+
+/// The prefix of a unit label holding logging settings, e.g. `"log: filter='debug'; console=false"`.
+pub const LOG_LABEL_PREFIX: &str = "log:";
+
+/// The output format of the log file and the console.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// A level name as written in the config, e.g. `"warn"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LevelName {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LevelName> for Level {
+    fn from(level: LevelName) -> Self {
+        match level {
+            LevelName::Error => Level::ERROR,
+            LevelName::Warn => Level::WARN,
+            LevelName::Info => Level::INFO,
+            LevelName::Debug => Level::DEBUG,
+            LevelName::Trace => Level::TRACE,
+        }
+    }
+}
+
+/// What happens to the log file at the end of the simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogAction {
+    Keep,
+    Delete,
+    /// Compress the file to `<file>.gz`.
+    Compress,
+}
+
+/// The lifecycle of the log files, applied when the last unit is called at the end of the simulation.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogRetention {
+    pub on_success: LogAction,
+    /// Used instead of `on_success` if the kernel reports an error.
+    pub on_error: LogAction,
+    /// The number of runs whose log files are kept in the log directory, 0 to keep all of them.
+    /// Only applies if the file name contains `{timestamp}`.
+    pub keep_runs: usize,
+}
+
+impl Default for LogRetention {
+    fn default() -> Self {
+        LogRetention {
+            on_success: LogAction::Delete,
+            on_error: LogAction::Keep,
+            keep_runs: 10,
+        }
+    }
+}
+
+impl LogRetention {
+    /// The policy of the JSON Lines file, which is written for the analysis of the run: it is kept
+    /// instead of deleted, but compressed and pruned like the log file.
+    pub fn for_json_lines(&self) -> LogRetention {
+        let keep = |action| match action {
+            LogAction::Delete => LogAction::Keep,
+            action => action,
+        };
+        LogRetention {
+            on_success: keep(self.on_success),
+            on_error: keep(self.on_error),
+            keep_runs: self.keep_runs,
+        }
+    }
+}
+
+/// Limits of the messages forwarded to the TRNSYS list file. The messages over the limits are
+/// still written to the log file. Errors are never suppressed.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ListFileLimits {
+    /// Messages of one callsite of one unit per time step.
+    pub per_callsite_per_timestep: u32,
+    /// Messages of one callsite of one unit per simulation.
+    pub per_callsite: u32,
+    /// Messages of all the units per time step.
+    pub per_timestep: u32,
+    /// Messages of all the units per simulation.
+    pub total: u32,
+}
+
+impl Default for ListFileLimits {
+    fn default() -> Self {
+        ListFileLimits {
+            per_callsite_per_timestep: 1,
+            per_callsite: 20,
+            per_timestep: 50,
+            total: 1000,
+        }
+    }
+}
+
+/// Settings of the logging subsystem.
+///
+/// The settings are merged from, in increasing priority:
+///
+/// 1. the defaults,
+/// 2. the file `trnsys_log.toml` next to the deck, or the file given in `TRNSYS_LOG_CONFIG`,
+/// 3. a label starting with `log:` of the first unit called in the simulation,
+/// 4. the environment variables `TRNSYS_LOG`, `TRNSYS_LOG_DIR`, `TRNSYS_LOG_FILE`,
+///    `TRNSYS_LOG_LEVEL`, `TRNSYS_LOG_CONSOLE` and `TRNSYS_LOG_FORMAT`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// Directory of the log file, with the same placeholders as the file name. Relative paths are
+    /// resolved against the directory of the deck.
+    pub dir: String,
+    /// Name of the log file, with the placeholders `{type}`, `{unit}`, `{deck}` and `{timestamp}`.
+    /// The timestamp is in milliseconds, and is increased if a run with the same timestamp exists.
+    pub file_name: String,
+    /// Filter directives in the `EnvFilter` syntax, e.g. `"info,trnsys_rust_template::solver=debug"`.
+    pub filter: String,
+    /// The lowest level forwarded to the TRNSYS list file.
+    pub trnsys_level: LevelName,
+    /// Whether the log is also written to stdout.
+    pub console: bool,
+    pub format: LogFormat,
+    /// Whether every event is also written to a JSON Lines file next to the log file.
+    pub json_lines: bool,
+    pub retention: LogRetention,
+    pub list_file: ListFileLimits,
+    /// The unit the config was loaded for, the `{unit}` of the file names.
+    #[serde(skip)]
+    pub unit: i32,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            dir: "{deck}_logs".to_string(),
+            file_name: "type_{type}_{timestamp}.log".to_string(),
+            #[cfg(debug_assertions)]
+            filter: "debug".to_string(),
+            #[cfg(not(debug_assertions))]
+            filter: "info".to_string(),
+            trnsys_level: LevelName::Info,
+            console: true,
+            format: LogFormat::Text,
+            json_lines: false,
+            retention: LogRetention::default(),
+            list_file: ListFileLimits::default(),
+            unit: 0,
+        }
+    }
+}
+
+impl LogConfig {
+    /// Loads the config from all the sources.
+    ///
+    /// It is called once per simulation, by the first unit the kernel calls: the logging is
+    /// shared by all the units, so the `log:` labels of the other units are not read, and
+    /// `{unit}` is the number of the first unit.
+    ///
+    /// Invalid sources are skipped, the problems are returned to be logged once the subscriber is
+    /// installed.
+    pub fn load(unit: i32) -> (Self, Vec<String>) {
+        let path = std::env::var_os("TRNSYS_LOG_CONFIG")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| deck_dir().join(LOG_CONFIG_FILE));
+        let file = path.is_file().then(|| {
+            let content = std::fs::read_to_string(&path).map_err(|e| e.to_string());
+            (path, content)
+        });
+        let labels: Vec<String> = (1..=get_number_of_labels(unit))
+            .map(|i| get_label(unit, i))
+            .collect();
+
+        let (mut config, problems) =
+            LogConfig::from_sources(file, &labels, |var| std::env::var(var).ok());
+        config.unit = unit;
+        (config, problems)
+    }
+
+    /// Merges the sources: the content of the config file read from its path, the labels of the
+    /// unit, of which only the ones starting with `log:` are read, and the environment variables
+    /// looked up by `env`.
+    fn from_sources(
+        file: Option<(PathBuf, Result<String, String>)>,
+        labels: &[String],
+        env: impl Fn(&str) -> Option<String>,
+    ) -> (Self, Vec<String>) {
+        let mut problems = vec![];
+        let mut table = toml::Table::new();
+
+        if let Some((path, content)) = file {
+            match content.and_then(|s| s.parse::<toml::Table>().map_err(|e| e.to_string())) {
+                Ok(file) => merge(&mut table, file),
+                Err(e) => problems.push(format!("Ignoring {}: {}", path.display(), e)),
+            }
+        }
+
+        for label in labels {
+            let Some(settings) = label.trim().strip_prefix(LOG_LABEL_PREFIX) else {
+                continue;
+            };
+            match settings.replace(';', "\n").parse::<toml::Table>() {
+                Ok(label) => merge(&mut table, label),
+                Err(e) => problems.push(format!("Ignoring label \"{}\": {}", label, e)),
+            }
+        }
+
+        for (var, key) in [
+            ("TRNSYS_LOG", "filter"),
+            ("TRNSYS_LOG_DIR", "dir"),
+            ("TRNSYS_LOG_FILE", "file_name"),
+            ("TRNSYS_LOG_LEVEL", "trnsys_level"),
+            ("TRNSYS_LOG_FORMAT", "format"),
+        ] {
+            if let Some(mut value) = env(var) {
+                if key == "trnsys_level" || key == "format" {
+                    value = value.to_lowercase();
+                }
+                table.insert(key.to_string(), value.into());
+            }
+        }
+        if let Some(value) = env("TRNSYS_LOG_CONSOLE") {
+            match value.parse::<bool>() {
+                Ok(console) => {
+                    table.insert("console".to_string(), console.into());
+                }
+                Err(e) => problems.push(format!("Ignoring TRNSYS_LOG_CONSOLE: {}", e)),
+            }
+        }
+
+        let config = table.try_into().unwrap_or_else(|e: toml::de::Error| {
+            problems.push(format!("Invalid logging config, using the defaults: {}", e));
+            LogConfig::default()
+        });
+        (config, problems)
+    }
+
+    /// The name of the log file of the run started at `timestamp`, with the placeholders replaced.
+    pub fn log_file_name(&self, timestamp: u128) -> String {
+        self.expand(&self.file_name, &timestamp.to_string())
+    }
+
+    /// The directory of the log files.
+    pub fn log_dir(&self) -> PathBuf {
+        deck_dir().join(self.expand(&self.dir, ""))
+    }
+
+    /// Whether the file name has a `{timestamp}`, so every run writes to a new file.
+    pub fn has_timestamp(&self) -> bool {
+        self.file_name.contains("{timestamp}")
+    }
+
+    /// The parts of the file name before and after `{timestamp}`, which the log files of all the
+    /// runs have in common. `None` if the file name has no timestamp.
+    pub fn file_name_pattern(&self) -> Option<(String, String)> {
+        let (prefix, suffix) = self.file_name.split_once("{timestamp}")?;
+        Some((self.expand(prefix, ""), self.expand(suffix, "")))
+    }
+
+    fn expand(&self, template: &str, timestamp: &str) -> String {
+        let deck = get_deck_filename();
+        let deck = Path::new(&deck)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        template
+            .replace("{type}", &TYPE_NUMBER.to_string())
+            .replace("{unit}", &self.unit.to_string())
+            .replace("{deck}", &deck)
+            .replace("{timestamp}", timestamp)
+    }
+}
+
+/// Merges `other` into `table`, nested tables key by key.
+fn merge(table: &mut toml::Table, other: toml::Table) {
+    for (key, value) in other {
+        match (table.get_mut(&key), value) {
+            (Some(toml::Value::Table(table)), toml::Value::Table(other)) => merge(table, other),
+            (_, value) => {
+                table.insert(key, value);
+            }
+        }
+    }
+}
+
+/// The directory of the deck, or the current directory if the kernel doesn't know it.
+fn deck_dir() -> PathBuf {
+    let dir = get_trnsys_input_file_dir();
+    if dir.is_empty() {
+        std::env::current_dir().unwrap_or(std::env::temp_dir())
+    } else {
+        PathBuf::from(dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(content: &str) -> Option<(PathBuf, Result<String, String>)> {
+        Some((PathBuf::from(LOG_CONFIG_FILE), Ok(content.to_string())))
+    }
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn defaults_without_sources() {
+        let (config, problems) = LogConfig::from_sources(None, &[], no_env);
+        assert!(problems.is_empty());
+        assert_eq!(config.file_name, LogConfig::default().file_name);
+        assert_eq!(config.retention.keep_runs, 10);
+    }
+
+    #[test]
+    fn environment_overrides_label_which_overrides_file() {
+        let labels = [
+            "Storage tank".to_string(),
+            "log: filter='debug'; console=false".to_string(),
+        ];
+        let env = |var: &str| (var == "TRNSYS_LOG").then(|| "trace".to_string());
+        let (config, problems) = LogConfig::from_sources(
+            file("filter = \"warn\"\nconsole = true\ntrnsys_level = \"error\""),
+            &labels,
+            env,
+        );
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(config.filter, "trace");
+        assert!(!config.console);
+        assert_eq!(config.trnsys_level, LevelName::Error);
+    }
+
+    #[test]
+    fn merges_nested_tables_key_by_key() {
+        let labels = ["log: retention = { keep_runs = 3 }".to_string()];
+        let (config, problems) = LogConfig::from_sources(
+            file("[retention]\non_success = \"compress\"\nkeep_runs = 5"),
+            &labels,
+            no_env,
+        );
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(config.retention.on_success, LogAction::Compress);
+        assert_eq!(config.retention.keep_runs, 3);
+    }
+
+    #[test]
+    fn environment_values_are_case_insensitive() {
+        let env = |var: &str| match var {
+            "TRNSYS_LOG_LEVEL" => Some("WARN".to_string()),
+            "TRNSYS_LOG_FORMAT" => Some("Json".to_string()),
+            "TRNSYS_LOG_CONSOLE" => Some("true".to_string()),
+            _ => None,
+        };
+        let (config, problems) = LogConfig::from_sources(None, &[], env);
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(config.trnsys_level, LevelName::Warn);
+        assert_eq!(config.format, LogFormat::Json);
+        assert!(config.console);
+    }
+
+    #[test]
+    fn invalid_sources_are_skipped() {
+        let labels = ["log: filter = ".to_string()];
+        let env = |var: &str| (var == "TRNSYS_LOG_CONSOLE").then(|| "yes".to_string());
+        let (config, problems) =
+            LogConfig::from_sources(file("filter = \"warn\"\nconsole = "), &labels, env);
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        assert_eq!(config.filter, LogConfig::default().filter);
+    }
+
+    #[test]
+    fn unknown_keys_fall_back_to_the_defaults() {
+        let (config, problems) =
+            LogConfig::from_sources(file("filter = \"warn\"\nfilters = \"info\""), &[], no_env);
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert_eq!(config.filter, LogConfig::default().filter);
+    }
+
+    #[test]
+    fn json_lines_are_kept_instead_of_deleted() {
+        let retention = LogRetention {
+            on_success: LogAction::Delete,
+            on_error: LogAction::Compress,
+            keep_runs: 3,
+        };
+        let json_lines = retention.for_json_lines();
+        assert_eq!(json_lines.on_success, LogAction::Keep);
+        assert_eq!(json_lines.on_error, LogAction::Compress);
+        assert_eq!(json_lines.keep_runs, 3);
+    }
+}
@@ -0,0 +1,205 @@
+use crate::trnsys::log_config::{LogAction, LogRetention};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Applies the retention policy to the closed log file of this run.
+///
+/// Returns the final path of the log file, or `None` if it was deleted.
+pub(crate) fn finish_log_file(
+    path: &Path,
+    retention: &LogRetention,
+    has_error: bool,
+) -> io::Result<Option<PathBuf>> {
+    let action = if has_error {
+        retention.on_error
+    } else {
+        retention.on_success
+    };
+    match action {
+        LogAction::Keep => Ok(Some(path.to_path_buf())),
+        LogAction::Delete => {
+            std::fs::remove_file(path)?;
+            Ok(None)
+        }
+        LogAction::Compress => {
+            let mut compressed = path.as_os_str().to_owned();
+            compressed.push(".gz");
+            let compressed = PathBuf::from(compressed);
+
+            let mut encoder = GzEncoder::new(File::create(&compressed)?, Compression::default());
+            io::copy(&mut File::open(path)?, &mut encoder)?;
+            encoder.finish()?;
+            std::fs::remove_file(path)?;
+            Ok(Some(compressed))
+        }
+    }
+}
+
+/// Deletes the oldest log files in `dir` whose name is `{prefix}{timestamp}{suffix}`, compressed or
+/// not, so that only the newest `keep_runs` remain.
+///
+/// The runs are ordered by the millisecond timestamp in their name rather than by modification
+/// time, which copying or compressing a file changes. Names with anything else than a timestamp
+/// between the prefix and the suffix are not log files of a run and are left alone.
+pub(crate) fn prune_old_runs(
+    dir: &Path,
+    (prefix, suffix): (&str, &str),
+    keep_runs: usize,
+) -> io::Result<()> {
+    if keep_runs == 0 {
+        return Ok(());
+    }
+    let mut runs = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if let Some(timestamp) = run_timestamp(&name, prefix, suffix) {
+            runs.push((timestamp, entry.path()));
+        }
+    }
+
+    runs.sort();
+    let outdated = runs.len().saturating_sub(keep_runs);
+    for (_, path) in runs.into_iter().take(outdated) {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// The millisecond timestamp of a log file named `{prefix}{timestamp}{suffix}`, compressed or not.
+fn run_timestamp(name: &str, prefix: &str, suffix: &str) -> Option<u128> {
+    let name = name.strip_suffix(".gz").unwrap_or(name);
+    let timestamp = name.strip_prefix(prefix)?.strip_suffix(suffix)?;
+    if timestamp.is_empty() || !timestamp.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    timestamp.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    /// An empty directory of its own for each test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("trnsys_log_retention_{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn retention(on_success: LogAction, on_error: LogAction) -> LogRetention {
+        LogRetention {
+            on_success,
+            on_error,
+            ..LogRetention::default()
+        }
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn keeps_the_log_file() {
+        let dir = test_dir("keep");
+        let path = dir.join("run.log");
+        std::fs::write(&path, "log").unwrap();
+
+        let retention = retention(LogAction::Delete, LogAction::Keep);
+        assert_eq!(
+            finish_log_file(&path, &retention, true).unwrap(),
+            Some(path.clone())
+        );
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "log");
+    }
+
+    #[test]
+    fn deletes_the_log_file() {
+        let dir = test_dir("delete");
+        let path = dir.join("run.log");
+        std::fs::write(&path, "log").unwrap();
+
+        let retention = retention(LogAction::Delete, LogAction::Keep);
+        assert_eq!(finish_log_file(&path, &retention, false).unwrap(), None);
+        assert!(file_names(&dir).is_empty());
+    }
+
+    #[test]
+    fn compresses_the_log_file() {
+        let dir = test_dir("compress");
+        let path = dir.join("run.log");
+        std::fs::write(&path, "log").unwrap();
+
+        let retention = retention(LogAction::Compress, LogAction::Keep);
+        let compressed = finish_log_file(&path, &retention, false).unwrap();
+        assert_eq!(compressed, Some(dir.join("run.log.gz")));
+        assert_eq!(file_names(&dir), ["run.log.gz"]);
+
+        let mut content = String::new();
+        GzDecoder::new(File::open(dir.join("run.log.gz")).unwrap())
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "log");
+    }
+
+    #[test]
+    fn prunes_the_oldest_runs_by_timestamp() {
+        let dir = test_dir("prune");
+        // Written newest first, so the modification times are in the opposite order.
+        for name in [
+            "deck_900.log",
+            "deck_1000.log.gz",
+            "deck_200.log",
+            "deck_30.log",
+            "deck_.log",
+            "deck_old.log",
+            "other_10.log",
+        ] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+
+        prune_old_runs(&dir, ("deck_", ".log"), 2).unwrap();
+        assert_eq!(
+            file_names(&dir),
+            [
+                "deck_.log",
+                "deck_1000.log.gz",
+                "deck_900.log",
+                "deck_old.log",
+                "other_10.log"
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_every_run_without_a_limit() {
+        let dir = test_dir("no_limit");
+        for name in ["deck_1.log", "deck_2.log"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+
+        prune_old_runs(&dir, ("deck_", ".log"), 0).unwrap();
+        assert_eq!(file_names(&dir), ["deck_1.log", "deck_2.log"]);
+    }
+
+    #[test]
+    fn only_timestamps_are_runs() {
+        assert_eq!(run_timestamp("deck_123.log", "deck_", ".log"), Some(123));
+        assert_eq!(run_timestamp("deck_123.log.gz", "deck_", ".log"), Some(123));
+        assert_eq!(run_timestamp("deck_.log", "deck_", ".log"), None);
+        assert_eq!(run_timestamp("deck_+12.log", "deck_", ".log"), None);
+        assert_eq!(run_timestamp("deck_12a.log", "deck_", ".log"), None);
+        assert_eq!(run_timestamp("deck_123.jsonl", "deck_", ".log"), None);
+    }
+}
@@ -1,37 +1,45 @@
-use crate::TYPE_NUMBER;
-use crate::trnsys::{Severity, get_current_unit, get_simulation_time, log_message, messages, simulation_has_error};
+use crate::trnsys::call_context::{CallPhase, CALL_SPAN_NAME};
+use crate::trnsys::json_lines::{json_lines_name, json_lines_path, JsonLinesLayer};
+use crate::trnsys::list_file_limiter::{allow_in_list_file, configure_list_file_limits};
+use crate::trnsys::log_config::{LogConfig, LogFormat, LogRetention};
+use crate::trnsys::log_retention::{finish_log_file, prune_old_runs};
+use crate::trnsys::{Severity, log_message, messages, simulation_has_error};
 use std::backtrace;
 use std::fmt::{Debug, Formatter, Pointer};
-use std::fs::OpenOptions;
+use std::fs::{File, OpenOptions};
 use std::io::{Cursor, Write};
-use std::sync::{Arc, LazyLock, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex, Once};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
 use tracing::{Event, Level, Subscriber, error};
 use tracing_subscriber::filter::EnvFilter;
-use tracing_subscriber::fmt::format::Writer;
 use tracing_subscriber::fmt::writer::MakeWriterExt;
-use tracing_subscriber::fmt::{FormatEvent, FormatFields, format, time};
+use tracing_subscriber::fmt::{format, time};
 use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::{
     Layer,
     fmt::{self, time::OffsetTime, writer::BoxMakeWriter},
-    layer::SubscriberExt,
+    layer::{Context, SubscriberExt},
     registry::Registry,
 };
 
 
-const TRNSYS_LOG_LEVEL: Level = Level::INFO;
-
-
 /// Custom function to handle trnsys logging.
 ///
 /// # Arguments
 ///
 /// * `level` - The level of the event.
-/// * `name` - The name of the event.
+/// * `error_code` - The error code of the event, if any.
+/// * `call` - The unit and type of the call the event belongs to.
 /// * `message` - The message of the event.
-pub fn log_in_trnsys(level: Level, error_code: Option<i32>, message: &str) {
+pub(crate) fn log_in_trnsys(
+    level: Level,
+    error_code: Option<i32>,
+    call: &CallFields,
+    message: &str,
+) {
     // Perform necessary actions here, such as sending notifications, writing to a dedicated log, raising alarms, etc.
     let trnsys_severity = match level {
         Level::ERROR => Severity::Fatal,
@@ -40,12 +48,28 @@ pub fn log_in_trnsys(level: Level, error_code: Option<i32>, message: &str) {
     };
     let error_code = error_code.unwrap_or(-1);
 
-    log_message(trnsys_severity, error_code, message);
+    log_message(
+        trnsys_severity,
+        error_code,
+        call.unit,
+        call.type_number,
+        message,
+    );
 }
 
+/// The reserved field whose value is passed to the kernel as the error code of the message,
+/// e.g. `warn!(trnsys.code = 12, "...")`.
+pub const TRNSYS_CODE_FIELD: &str = "trnsys.code";
+
+/// The reserved field of events which were already reported to the kernel, e.g. through
+/// `found_bad_input`, and are only written to the log files: `error!(trnsys.reported = true, "...")`.
+pub const TRNSYS_REPORTED_FIELD: &str = "trnsys.reported";
+
 struct MessageCollector {
     message: Option<String>,
     fields: Vec<(String, String)>,
+    error_code: Option<i32>,
+    reported: bool,
 }
 
 impl MessageCollector {
@@ -53,33 +77,135 @@ impl MessageCollector {
         MessageCollector {
             message: None,
             fields: Vec::new(),
+            error_code: None,
+            reported: false,
         }
     }
 
+    /// Replaces the `{name}` placeholders of the message with the fields, and appends the other
+    /// fields as `key=value`.
     fn get_formatted_message(&self) -> Option<String> {
-        if let Some(message) = &self.message {
-            let mut formatted_message = message.clone();
-            for (name, value) in &self.fields {
-                formatted_message = formatted_message.replace(&format!("{{{}}}", name), value);
+        let mut formatted_message = self.message.clone()?;
+        let mut remaining = vec![];
+        for (name, value) in &self.fields {
+            let placeholder = format!("{{{}}}", name);
+            if formatted_message.contains(&placeholder) {
+                formatted_message = formatted_message.replace(&placeholder, value);
+            } else {
+                remaining.push(format!("{}={}", name, value));
             }
-            Some(formatted_message)
+        }
+        if !remaining.is_empty() {
+            formatted_message.push(' ');
+            formatted_message.push_str(&remaining.join(" "));
+        }
+        Some(formatted_message)
+    }
+
+    fn record_value(&mut self, field: &Field, value: String) {
+        if field.name() == "message" {
+            self.message = Some(value);
         } else {
-            None
+            self.fields.push((field.name().to_string(), value));
         }
     }
 }
 
 impl Visit for MessageCollector {
-    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
-        let val_str = format!("{:?}", value);
-        if field.name() == "message" {
-            // message字段通常是一个静态字符串，如 "a: {a}"
-            self.message = Some(val_str.trim_matches('"').to_string());
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        if field.name() == TRNSYS_CODE_FIELD {
+            self.error_code = i32::try_from(value).ok();
+        } else {
+            self.record_value(field, value.to_string());
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == TRNSYS_CODE_FIELD {
+            self.error_code = i32::try_from(value).ok();
         } else {
-            // 其他字段存储参数值
-            self.fields.push((field.name().to_string(), val_str));
+            self.record_value(field, value.to_string());
         }
     }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        if field.name() == TRNSYS_REPORTED_FIELD {
+            self.reported = value;
+        } else {
+            self.record_value(field, value.to_string());
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record_value(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        // the message is a `fmt::Arguments`, whose `Debug` output is the formatted message
+        self.record_value(field, format!("{:?}", value));
+    }
+}
+
+/// The fields of the call span opened by `entrance()`, see [`CallContext::span`].
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CallFields {
+    pub unit: i32,
+    pub type_number: i32,
+    pub phase: Option<CallPhase>,
+    pub time: f64,
+    pub iteration: i32,
+}
+
+impl CallFields {
+    /// The fields of the innermost call span around the event, if any.
+    pub fn of_event<S>(event: &Event<'_>, ctx: &Context<'_, S>) -> Option<Self>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        ctx.event_scope(event)?
+            .find_map(|span| span.extensions().get::<CallFields>().cloned())
+    }
+}
+
+impl Visit for CallFields {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if field.name() == "time" {
+            self.time = value;
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        match field.name() {
+            "unit" => self.unit = value as i32,
+            "type_number" => self.type_number = value as i32,
+            "iteration" => self.iteration = value as i32,
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "phase" {
+            // matched by name rather than formatted into a `String` at every call
+            self.phase = CallPhase::ALL
+                .into_iter()
+                .find(|phase| debug_eq(value, phase.name()));
+        }
+    }
+}
+
+/// Whether the `Debug` output of `value` is `expected`, without formatting it into a `String`.
+fn debug_eq(value: &dyn Debug, expected: &str) -> bool {
+    struct Rest<'a>(&'a str);
+
+    impl std::fmt::Write for Rest<'_> {
+        fn write_str(&mut self, s: &str) -> std::fmt::Result {
+            self.0 = self.0.strip_prefix(s).ok_or(std::fmt::Error)?;
+            Ok(())
+        }
+    }
+
+    let mut rest = Rest(expected);
+    std::fmt::Write::write_fmt(&mut rest, format_args!("{:?}", value)).is_ok() && rest.0.is_empty()
 }
 
 /// Custom Layer to intercept high-priority events.
@@ -102,21 +228,45 @@ impl TrnSysLogLayer {
     }
 }
 
-impl<S: Subscriber> Layer<S> for TrnSysLogLayer {
+impl<S> Layer<S> for TrnSysLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    /// Keeps the fields of call spans, so that events can be attributed to their call without
+    /// asking the kernel.
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if attrs.metadata().name() != CALL_SPAN_NAME {
+            return;
+        }
+        let mut fields = CallFields::default();
+        attrs.record(&mut fields);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(fields);
+        }
+    }
+
     /// Intercepts events and calls the custom function if the event level is above the threshold.
     ///
     /// # Arguments
     ///
     /// * `event` - The event to be processed.
-    /// * `_ctx` - The context of the subscriber.
-    fn on_event(&self, event: &Event<'_>, _ctx: tracing_subscriber::layer::Context<'_, S>) {
+    /// * `ctx` - The context of the subscriber.
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
         let metadata = event.metadata();
-        if metadata.level() <= &self.threshold {
+        if metadata.level() > &self.threshold {
+            return;
+        }
+        let mut collector = MessageCollector::new();
+        event.record(&mut collector);
+        if collector.reported {
+            return;
+        }
+        // events outside of a call span, e.g. in tests, are not attributed to a unit
+        let call = CallFields::of_event(event, &ctx).unwrap_or_default();
+        if allow_in_list_file(metadata, call.unit, call.time) {
             let mut str = String::new();
             let mut writer = tracing_subscriber::fmt::format::Writer::new(&mut str);
 
-            let mut collector = MessageCollector::new();
-            event.record(&mut collector);
             let formatted_msg = collector.get_formatted_message();
             if let Some(msg) = formatted_msg {
                 write!(
@@ -130,107 +280,313 @@ impl<S: Subscriber> Layer<S> for TrnSysLogLayer {
                 .unwrap();
             }
 
-            log_in_trnsys(metadata.level().clone(), None, &str);
+            log_in_trnsys(*metadata.level(), collector.error_code, &call, &str);
         }
     }
 }
 
-/// Returns the default log file name under the current directory.
-pub fn get_default_log_file() -> String {
-
-    let file_name = format!(
-        "type_{}_{}.log",
-        TYPE_NUMBER,
-        get_current_unit()
-    );
-    let cwd = std::env::current_dir().unwrap_or(std::env::temp_dir());
-    
-    cwd.join(file_name)
-        .to_str()
-        .expect("Failed to get log file name")
-        .to_string()
+/// A log file of this run. The file is closed at the end of the simulation, so that the
+/// retention policy can delete or compress it.
+struct LogFile {
+    path: PathBuf,
+    file: Option<File>,
+    /// The parts of the file name around the timestamp, see [`LogConfig::file_name_pattern`].
+    pattern: Option<(String, String)>,
+}
 
+/// The log files of this run: the log file, then the JSON Lines file if enabled.
+struct ActiveLog {
+    files: Vec<LogFile>,
+    dir: PathBuf,
+    retention: LogRetention,
 }
 
-struct UnitNoFmt<F>(F);
+static ACTIVE_LOG: LazyLock<Mutex<Option<ActiveLog>>> = LazyLock::new(|| Mutex::new(None));
 
-impl<S, N, F> FormatEvent<S, N> for UnitNoFmt<F>
-where
-    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
-    N: for<'writer> FormatFields<'writer> + 'static,
-    F: FormatEvent<S, N>,
-{
-    fn format_event(
-        &self,
-        ctx: &tracing_subscriber::fmt::FmtContext<'_, S, N>,
-        mut writer: Writer<'_>,
-        event: &Event<'_>,
-    ) -> std::fmt::Result {
-        write!(writer, "[Unit {}] [T={}]", get_current_unit(), get_simulation_time())?;
+/// The index of the log file in [`ActiveLog::files`].
+const LOG_FILE: usize = 0;
+/// The index of the JSON Lines file in [`ActiveLog::files`].
+const JSON_LINES_FILE: usize = 1;
 
-        self.0.format_event(ctx, writer.by_ref(), event)?;
+/// Writes to one of the active log files, or nowhere once it is closed.
+pub(crate) struct LogFileWriter(usize);
 
-        Ok(())
+impl LogFileWriter {
+    fn with_file<R>(
+        &self,
+        f: impl FnOnce(&mut File) -> std::io::Result<R>,
+        closed: R,
+    ) -> std::io::Result<R> {
+        let mut log = ACTIVE_LOG.lock().unwrap_or_else(|e| e.into_inner());
+        match log
+            .as_mut()
+            .and_then(|log| log.files.get_mut(self.0)?.file.as_mut())
+        {
+            Some(file) => f(file),
+            None => Ok(closed),
+        }
     }
 }
 
-static LOGFILE_PATH: LazyLock<Mutex<Option<String>>> = LazyLock::new(|| Mutex::new(None));
+impl Write for LogFileWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.with_file(|file| file.write(buf), buf.len())
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        // under a single lock, so that lines of different threads are not interleaved
+        self.with_file(|file| file.write_all(buf), ())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.with_file(|file| file.flush(), ())
+    }
+}
 
 pub fn is_tracing_initialized() -> bool {
-    LOGFILE_PATH.try_lock().map(|l|l.is_some()).unwrap_or(true)
+    ACTIVE_LOG.try_lock().map(|l|l.is_some()).unwrap_or(true)
+}
+
+/// Closes the log files and applies the retention policy, called once all the units have been
+/// called for the last time.
+///
+/// The final paths of the log files are printed to the TrnSys list file, as messages of the unit
+/// called last.
+pub fn close_log_file(has_error: bool, unit: i32, type_number: i32) {
+    let Some((files, dir, retention)) = ACTIVE_LOG
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_mut()
+        .map(|log| {
+            let files: Vec<_> = log
+                .files
+                .iter_mut()
+                .enumerate()
+                // dropping the file closes it
+                .filter_map(|(i, file)| {
+                    file.file.take()?;
+                    Some((i, file.path.clone(), file.pattern.clone()))
+                })
+                .collect();
+            (files, log.dir.clone(), log.retention.clone())
+        })
+    else {
+        return;
+    };
+
+    for (i, path, pattern) in files {
+        let retention = match i {
+            JSON_LINES_FILE => retention.for_json_lines(),
+            _ => retention.clone(),
+        };
+        match finish_log_file(&path, &retention, has_error) {
+            Ok(Some(path)) => log_message(
+                Severity::Notice,
+                -1,
+                unit,
+                type_number,
+                &format!("Log file: {}", path.display()),
+            ),
+            Ok(None) => {}
+            Err(e) => log_message(
+                Severity::Warning,
+                -1,
+                unit,
+                type_number,
+                &format!(
+                    "Cannot apply the retention policy to {}: {}",
+                    path.display(),
+                    e
+                ),
+            ),
+        }
+        if let Some((prefix, suffix)) = &pattern {
+            if let Err(e) = prune_old_runs(&dir, (prefix, suffix), retention.keep_runs) {
+                log_message(
+                    Severity::Warning,
+                    -1,
+                    unit,
+                    type_number,
+                    &format!("Cannot remove old log files from {}: {}", dir.display(), e),
+                );
+            }
+        }
+    }
+}
+
+/// Creates the log file of this run in `dir`.
+///
+/// With a `{timestamp}` in the file name, the file must not exist yet: a run started in the same
+/// millisecond as another one takes the next free timestamp instead of overwriting its log.
+/// Without one, the log of the previous run is overwritten.
+fn create_log_file(config: &LogConfig, dir: &Path) -> std::io::Result<(PathBuf, File)> {
+    std::fs::create_dir_all(dir)?;
+    let mut timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    loop {
+        let path = dir.join(config.log_file_name(timestamp));
+        match open_log_file(&path, config.has_timestamp()) {
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => timestamp += 1,
+            file => return file.map(|file| (path, file)),
+        }
+    }
 }
 
+/// Creates a log file, or truncates it if `create_new` is false.
+fn open_log_file(path: &Path, create_new: bool) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if create_new {
+        options.create_new(true);
+    } else {
+        options.create(true).truncate(true);
+    }
+    options.open(path)
+}
+
+static SUBSCRIBER: Once = Once::new();
+
 /// Initializes tracing with custom layers and settings.
 ///
+/// It is called at the start of every simulation: the log files of the simulation are opened,
+/// while the subscriber is only installed by the first simulation of the process, so its filter
+/// and formats don't change afterward. If another subscriber is already installed, e.g. by a test
+/// harness, it is kept.
+///
+/// If the log file cannot be created, e.g. in a read-only directory, it is created in the
+/// temporary directory instead, and if that fails too, the logs only go to the list file and the
+/// console. The simulation runs either way.
+///
 /// # Arguments
 ///
-/// * `file_name` - The name of the log file to write to.
-pub fn init_tracing(file_name: Option<String>) {
-    let file_name = file_name.unwrap_or(get_default_log_file());
-
-    // Store the log file path for later use
-    let mut log_file_path = LOGFILE_PATH.lock().unwrap();
-    *log_file_path = Some(file_name.clone());
-
-    // Open (or create) the log file
-    let log_file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .open(file_name)
-        .expect("Failed to open log file");
-
-    // Wrap the writer with a Mutex to ensure thread-safe writing
-    let file_writer = BoxMakeWriter::new(log_file);
-
-    let local_time = OffsetTime::local_rfc_3339().expect("Failed to get local time offset");
-
-    // Set up the filter (can be controlled via the RUST_LOG environment variable)
-    #[cfg(debug_assertions)]
-    let filter = EnvFilter::from_env("TRNSYS_ODBC_RS").add_directive("debug".parse().unwrap());
-    #[cfg(not(debug_assertions))]
-    let filter = EnvFilter::from_env("TRNSYS_ODBC_RS").add_directive("info".parse().unwrap());
-
-    // Formatting Layer: output to both file and stdout
+/// * `config` - The logging settings, see [`LogConfig::load`].
+///
+/// # Returns
+///
+/// The problems creating the log files, to be logged once the subscriber is installed.
+pub fn init_tracing(config: LogConfig) -> Vec<String> {
+    let mut problems = vec![];
+    let mut dir = config.log_dir();
+    let created = create_log_file(&config, &dir).or_else(|e| {
+        let temp_dir = std::env::temp_dir();
+        problems.push(format!(
+            "Cannot create the log file in {}, using {} instead: {}",
+            dir.display(),
+            temp_dir.display(),
+            e
+        ));
+        dir = temp_dir;
+        create_log_file(&config, &dir)
+    });
+    let (path, file) = match created {
+        Ok((path, file)) => (path, Some(file)),
+        Err(e) => {
+            problems.push(format!(
+                "Cannot create the log file in {}, logging to the list file only: {}",
+                dir.display(),
+                e
+            ));
+            (PathBuf::new(), None)
+        }
+    };
+
+    let pattern = config.file_name_pattern();
+    let mut files = vec![LogFile {
+        file,
+        path,
+        pattern: pattern.clone(),
+    }];
+    if config.json_lines {
+        let path = json_lines_path(&files[LOG_FILE].path);
+        // only next to a log file, so that it is found
+        let file = match &files[LOG_FILE].file {
+            Some(_) => open_log_file(&path, config.has_timestamp())
+                .map_err(|e| problems.push(format!("Cannot create {}: {}", path.display(), e)))
+                .ok(),
+            None => None,
+        };
+        files.push(LogFile {
+            file,
+            path,
+            pattern: pattern.map(|(prefix, suffix)| (prefix, json_lines_name(&suffix))),
+        });
+    }
+
+    // Store the log files for later use, the files of a previous simulation are closed
+    *ACTIVE_LOG.lock().unwrap_or_else(|e| e.into_inner()) = Some(ActiveLog {
+        files,
+        dir,
+        retention: config.retention.clone(),
+    });
+    configure_list_file_limits(config.list_file.clone());
+
+    SUBSCRIBER.call_once(|| install_subscriber(&config));
+    problems
+}
+
+/// The timestamps of the log, in local time if the offset is known. On Linux, the local offset
+/// cannot be determined once the process has several threads, e.g. in `cargo test`.
+enum LogTimer<L, U> {
+    Local(L),
+    Utc(U),
+}
+
+impl<L: time::FormatTime, U: time::FormatTime> time::FormatTime for LogTimer<L, U> {
+    fn format_time(&self, w: &mut format::Writer<'_>) -> std::fmt::Result {
+        match self {
+            LogTimer::Local(timer) => timer.format_time(w),
+            LogTimer::Utc(timer) => timer.format_time(w),
+        }
+    }
+}
+
+fn install_subscriber(config: &LogConfig) {
+    // The writer locks the file, to ensure thread-safe writing
+    let file_writer = BoxMakeWriter::new(|| LogFileWriter(LOG_FILE));
+    let writer = if config.console {
+        BoxMakeWriter::new(file_writer.and(std::io::stdout))
+    } else {
+        file_writer
+    };
+
+    let timer = match OffsetTime::local_rfc_3339() {
+        Ok(local_time) => LogTimer::Local(local_time),
+        Err(_) => LogTimer::Utc(time::UtcTime::rfc_3339()),
+    };
+
+    let filter = EnvFilter::builder().parse_lossy(&config.filter);
+
+    // Formatting Layer: output to the file and optionally to stdout
     let fmt_layer = fmt::layer()
-        .with_writer(file_writer.and(std::io::stdout))
-        .with_timer(local_time)
+        .with_writer(writer)
+        .with_timer(timer)
         .with_line_number(true)
         .with_file(true)
-        .with_ansi(false)
-        .event_format(UnitNoFmt(fmt::format()));
+        .with_ansi(false);
+    let fmt_layer = match config.format {
+        LogFormat::Text => fmt_layer.boxed(),
+        LogFormat::Json => fmt_layer.json().boxed(),
+    };
 
-    let trnsys_log_layer = TrnSysLogLayer::new(TRNSYS_LOG_LEVEL);
+    let trnsys_log_layer = TrnSysLogLayer::new(config.trnsys_level.into());
 
     // Combine layers
     let subscriber = Registry::default()
         .with(filter)
         .with(trnsys_log_layer)
-        .with(fmt_layer);
-
-    // Global initialization
-    tracing::subscriber::set_global_default(subscriber)
-        .expect("Unable to set global tracing subscriber");
+        .with(fmt_layer)
+        .with(
+            config
+                .json_lines
+                .then(|| JsonLinesLayer::new(|| LogFileWriter(JSON_LINES_FILE))),
+        );
+
+    // Global initialization, an existing subscriber is kept
+    if tracing::subscriber::set_global_default(subscriber).is_err() {
+        return;
+    }
 
     // panic hook
     std::panic::set_hook(Box::new(|panic_info| {
@@ -239,3 +595,66 @@ pub fn init_tracing(file_name: Option<String>) {
     }));
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::warn;
+
+    /// The rendered message and the error code of each event.
+    type Collected = Vec<(Option<String>, Option<i32>)>;
+
+    struct Collect(Arc<Mutex<Collected>>);
+
+    impl<S: Subscriber> Layer<S> for Collect {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            let mut collector = MessageCollector::new();
+            event.record(&mut collector);
+            let message = collector.get_formatted_message();
+            self.0.lock().unwrap().push((message, collector.error_code));
+        }
+    }
+
+    fn collect(events: impl FnOnce()) -> Collected {
+        let collected = Arc::new(Mutex::new(vec![]));
+        let subscriber = Registry::default().with(Collect(collected.clone()));
+        tracing::subscriber::with_default(subscriber, events);
+        Arc::try_unwrap(collected).unwrap().into_inner().unwrap()
+    }
+
+    #[test]
+    fn renders_the_fields_as_placeholders_or_key_value() {
+        let flow = 0.01;
+        let collected = collect(|| {
+            warn!(flow, "Flow rate below the minimum");
+            warn!(flow, pump = "P1", "{{pump}} stopped");
+            warn!(pump = ?"P1", "{{pump}} {{pump}}");
+        });
+        assert_eq!(
+            collected,
+            [
+                (Some("Flow rate below the minimum flow=0.01".into()), None),
+                (Some("P1 stopped flow=0.01".into()), None),
+                (Some("\"P1\" \"P1\"".into()), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn takes_the_error_code_from_the_trnsys_code_field() {
+        let collected = collect(|| {
+            warn!(trnsys.code = 12, "signed");
+            warn!(trnsys.code = 13u64, "unsigned");
+            warn!(trnsys.code = u64::MAX, "out of range");
+            warn!(code = 14, "other field");
+        });
+        assert_eq!(
+            collected,
+            [
+                (Some("signed".into()), Some(12)),
+                (Some("unsigned".into()), Some(13)),
+                (Some("out of range".into()), None),
+                (Some("other field code=14".into()), None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,877 @@
+#![allow(non_snake_case)]
+
+//! A kernel written in Rust, which stands in for `TRNDll64.dll` where TRNSYS doesn't run.
+//!
+//! It defines the functions declared in `ext_c`, under the same symbols, so the Type links without
+//! TRNSYS, e.g. on Linux or with the `mock-kernel` feature. The tests play the part of the
+//! simulation: they fill the deck and the simulation time in the [`MockKernel`] of their thread,
+//! call the Type with [`call_type`], and check what the Type declared and set:
+//!
+//! ```ignore
+//! with_mock_kernel(|kernel| {
+//!     kernel.params = vec![3.];
+//!     kernel.inputs = vec![1.];
+//!     kernel.num_outputs = 1;
+//! });
+//! call_type(CallPhase::VersionSigning);
+//! call_type(CallPhase::FirstCall);
+//! call_type(CallPhase::StartTime);
+//! call_type(CallPhase::Iteration);
+//! assert_eq!(with_mock_kernel(|kernel| kernel.outputs[0]), 3.);
+//! ```
+//!
+//! The TRNSYS subroutines which need the data of a TRNSYS installation, like the fluid
+//! properties, are not available and report an error.
+
+use crate::trnsys::fortran_string::truncate_utf8;
+use crate::trnsys::CallPhase;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::os::raw::{c_char, c_double, c_int};
+
+/// A message the Type wrote to the list file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockMessage {
+    pub code: i32,
+    /// As passed by the Type, e.g. `"Warning"` or `"FATAL"`.
+    pub severity: String,
+    pub message: String,
+    pub unit: i32,
+    pub type_number: i32,
+}
+
+/// An input or parameter the Type reported as bad.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BadValue {
+    /// The 1-based index, as the kernel sees it.
+    pub index: i32,
+    pub severity: String,
+    pub message: String,
+}
+
+/// What the Type declared at the first call, `None` until it is declared.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MockDeclarations {
+    pub type_version: Option<i32>,
+    pub params: Option<i32>,
+    pub inputs: Option<i32>,
+    pub outputs: Option<i32>,
+    pub derivatives: Option<i32>,
+    pub discrete_controls: Option<i32>,
+    pub static_storage: Option<i32>,
+    pub dynamic_storage: Option<i32>,
+    pub iteration_mode: Option<i32>,
+    pub input_units: BTreeMap<i32, String>,
+    pub output_units: BTreeMap<i32, String>,
+}
+
+/// The simulation as the mock kernel tells it to the Type, and what the Type told the kernel.
+/// Indices of the vectors are 0-based, the kernel functions take 1-based indices.
+#[derive(Debug, Clone)]
+pub struct MockKernel {
+    // --- the simulation, set by the test ---------------------------------------------------------
+    pub phase: CallPhase,
+    pub unit: i32,
+    pub type_number: i32,
+    pub time: f64,
+    pub timestep: f64,
+    pub start_time: f64,
+    pub stop_time: f64,
+    pub iteration: i32,
+    pub convergence_tolerance: f64,
+    pub minimum_timestep: f64,
+    pub params: Vec<f64>,
+    pub inputs: Vec<f64>,
+    /// The number of outputs in the deck.
+    pub num_outputs: i32,
+    /// The number of derivatives in the deck.
+    pub num_derivatives: i32,
+    pub numerical_solution: Vec<f64>,
+    pub previous_control_states: Vec<i32>,
+    pub labels: Vec<String>,
+    pub formats: Vec<String>,
+    pub deck_file: String,
+    pub input_file_dir: String,
+    pub root_dir: String,
+    /// The files assigned to logical units in the deck.
+    pub logical_unit_files: BTreeMap<i32, String>,
+    pub next_logical_unit: i32,
+    pub max_path_length: i32,
+    pub max_label_length: i32,
+    pub max_descrip_length: i32,
+
+    // --- set by the Type ---------------------------------------------------------------------------
+    pub declared: MockDeclarations,
+    pub outputs: Vec<f64>,
+    pub static_storage: Vec<f64>,
+    /// The values of the dynamic storage at the end of the last time step.
+    pub dynamic_storage: Vec<f64>,
+    pub dynamic_storage_this_iteration: Vec<f64>,
+    pub numerical_derivatives: Vec<f64>,
+    pub desired_control_states: Vec<i32>,
+    pub messages: Vec<MockMessage>,
+    pub bad_inputs: Vec<BadValue>,
+    pub bad_parameters: Vec<BadValue>,
+    /// Set by fatal messages and bad values, like the error flag of the kernel.
+    pub error_found: bool,
+}
+
+impl Default for MockKernel {
+    fn default() -> Self {
+        MockKernel {
+            phase: CallPhase::VersionSigning,
+            unit: 1,
+            type_number: crate::TYPE_NUMBER as i32,
+            time: 0.,
+            timestep: 1.,
+            start_time: 0.,
+            stop_time: 8760.,
+            iteration: 1,
+            convergence_tolerance: 0.001,
+            minimum_timestep: 1. / 3600.,
+            params: Vec::new(),
+            inputs: Vec::new(),
+            num_outputs: 0,
+            num_derivatives: 0,
+            numerical_solution: Vec::new(),
+            previous_control_states: Vec::new(),
+            labels: Vec::new(),
+            formats: Vec::new(),
+            deck_file: String::new(),
+            input_file_dir: String::new(),
+            root_dir: String::new(),
+            logical_unit_files: BTreeMap::new(),
+            next_logical_unit: 30,
+            max_path_length: 300,
+            max_label_length: 300,
+            max_descrip_length: 300,
+            declared: MockDeclarations::default(),
+            outputs: Vec::new(),
+            static_storage: Vec::new(),
+            dynamic_storage: Vec::new(),
+            dynamic_storage_this_iteration: Vec::new(),
+            numerical_derivatives: Vec::new(),
+            desired_control_states: Vec::new(),
+            messages: Vec::new(),
+            bad_inputs: Vec::new(),
+            bad_parameters: Vec::new(),
+            error_found: false,
+        }
+    }
+}
+
+impl MockKernel {
+    /// Moves to the next time step: the dynamic storage of this iteration becomes the value of the
+    /// last time step, and the iterations start again.
+    pub fn advance_timestep(&mut self) {
+        self.dynamic_storage
+            .clone_from(&self.dynamic_storage_this_iteration);
+        self.time += self.timestep;
+        self.iteration = 1;
+    }
+
+    fn report(&mut self, code: i32, severity: &str, message: String) {
+        self.error_found |= is_error(severity);
+        self.messages.push(MockMessage {
+            code,
+            severity: severity.to_string(),
+            message,
+            unit: self.unit,
+            type_number: self.type_number,
+        });
+    }
+
+    /// Reports an index the kernel would reject, and returns the default value.
+    fn out_of_range<T: Default>(
+        &mut self,
+        function: &str,
+        kind: &str,
+        i: c_int,
+        count: usize,
+    ) -> T {
+        self.report(
+            -1,
+            "FATAL",
+            format!("{function}: {kind} {i} is out of range, there are {count}"),
+        );
+        T::default()
+    }
+
+    fn unavailable(&mut self, function: &str) {
+        self.report(
+            -1,
+            "FATAL",
+            format!("{function} is not available in the mock kernel"),
+        );
+    }
+
+    /// Reads the value at the 1-based index `i` of the vector selected by `values`.
+    fn get<T: Copy + Default>(
+        &mut self,
+        function: &str,
+        kind: &str,
+        i: c_int,
+        values: fn(&MockKernel) -> &[T],
+    ) -> T {
+        let values = values(self);
+        match index(i, values.len()) {
+            Some(index) => values[index],
+            None => {
+                let count = values.len();
+                self.out_of_range(function, kind, i, count)
+            }
+        }
+    }
+
+    /// Writes the value at the 1-based index `i` of the vector selected by `values`.
+    fn set<T>(
+        &mut self,
+        function: &str,
+        kind: &str,
+        i: c_int,
+        value: T,
+        values: fn(&mut MockKernel) -> &mut Vec<T>,
+    ) {
+        let values = values(self);
+        match index(i, values.len()) {
+            Some(index) => values[index] = value,
+            None => {
+                let count = values.len();
+                self.out_of_range::<()>(function, kind, i, count)
+            }
+        }
+    }
+}
+
+thread_local! {
+    static MOCK_KERNEL: RefCell<MockKernel> = RefCell::new(MockKernel::default());
+}
+
+/// Gives access to the mock kernel of the current thread. The Type must not be called from `f`.
+pub fn with_mock_kernel<R>(f: impl FnOnce(&mut MockKernel) -> R) -> R {
+    MOCK_KERNEL.with(|kernel| f(&mut kernel.borrow_mut()))
+}
+
+/// Calls the Type in the given phase, like the kernel does.
+pub fn call_type(phase: CallPhase) {
+    with_mock_kernel(|kernel| kernel.phase = phase);
+    crate::entrance();
+}
+
+/// The 0-based index of a 1-based kernel index, if it is in range.
+fn index(i: c_int, len: usize) -> Option<usize> {
+    usize::try_from(i)
+        .ok()
+        .and_then(|i| i.checked_sub(1))
+        .filter(|&i| i < len)
+}
+
+fn is_error(severity: &str) -> bool {
+    severity.eq_ignore_ascii_case("fatal") || severity.eq_ignore_ascii_case("stop")
+}
+
+fn flag(set: bool) -> c_int {
+    set as c_int
+}
+
+/// Reads a Fortran `CHARACTER` argument, without the trailing blanks.
+unsafe fn read_string(string: *const c_char, len: usize) -> String {
+    let bytes = std::slice::from_raw_parts(string as *const u8, len);
+    String::from_utf8_lossy(bytes)
+        .trim_end_matches([' ', '\0'])
+        .to_string()
+}
+
+/// Returns a Fortran `CHARACTER` result: the buffer is filled with the string and blanks.
+unsafe fn write_string(buffer: *mut c_char, len: usize, s: &str) -> *mut c_char {
+    let bytes = std::slice::from_raw_parts_mut(buffer as *mut u8, len);
+    let s = truncate_utf8(s, len);
+    bytes.fill(b' ');
+    bytes[..s.len()].copy_from_slice(s.as_bytes());
+    buffer
+}
+
+/// The `no`-th entry of `values`, 1-based, or an empty string.
+fn nth(values: &[String], no: c_int) -> &str {
+    index(no, values.len()).map_or("", |i| values[i].as_str())
+}
+
+// --- Kernel subroutines ----------------------------------------------------------------------------
+
+#[unsafe(export_name = kernel_symbol!(FOUNDBADINPUT))]
+pub unsafe extern "C" fn FOUNDBADINPUT(
+    Input: *mut c_int,
+    Severity: *mut c_char,
+    Message: *mut c_char,
+    Sevlen: usize,
+    Messlen: usize,
+) {
+    let bad = BadValue {
+        index: *Input,
+        severity: read_string(Severity, Sevlen),
+        message: read_string(Message, Messlen),
+    };
+    with_mock_kernel(|kernel| {
+        kernel.error_found |= is_error(&bad.severity);
+        kernel.bad_inputs.push(bad);
+    })
+}
+
+#[unsafe(export_name = kernel_symbol!(FOUNDBADPARAMETER))]
+pub unsafe extern "C" fn FOUNDBADPARAMETER(
+    Param: *mut c_int,
+    Severity: *mut c_char,
+    Message: *mut c_char,
+    Sevlen: usize,
+    Messlen: usize,
+) {
+    let bad = BadValue {
+        index: *Param,
+        severity: read_string(Severity, Sevlen),
+        message: read_string(Message, Messlen),
+    };
+    with_mock_kernel(|kernel| {
+        kernel.error_found |= is_error(&bad.severity);
+        kernel.bad_parameters.push(bad);
+    })
+}
+
+/// The mock kernel doesn't open the files of the deck, every file is at its end.
+#[unsafe(export_name = kernel_symbol!(READNEXTCHAR))]
+pub unsafe extern "C" fn READNEXTCHAR(lun: *mut c_int) -> c_int {
+    -1
+}
+
+#[unsafe(export_name = kernel_symbol!(SETDESIREDDISCRETECONTROLSTATE))]
+pub unsafe extern "C" fn SETDESIREDDISCRETECONTROLSTATE(i: *mut c_int, j: *mut c_int) {
+    with_mock_kernel(|kernel| {
+        kernel.set("SETDESIREDDISCRETECONTROLSTATE", "control", *i, *j, |k| {
+            &mut k.desired_control_states
+        })
+    })
+}
+
+#[unsafe(export_name = kernel_symbol!(SETDYNAMICARRAYINITIALVALUE))]
+pub unsafe extern "C" fn SETDYNAMICARRAYINITIALVALUE(i: *mut c_int, Value: *mut c_double) {
+    with_mock_kernel(|kernel| {
+        kernel.set(
+            "SETDYNAMICARRAYINITIALVALUE",
+            "dynamic storage",
+            *i,
+            *Value,
+            |k| &mut k.dynamic_storage_this_iteration,
+        );
+        kernel
+            .dynamic_storage
+            .clone_from(&kernel.dynamic_storage_this_iteration);
+    })
+}
+
+#[unsafe(export_name = kernel_symbol!(SETDYNAMICARRAYVALUETHISITERATION))]
+pub unsafe extern "C" fn SETDYNAMICARRAYVALUETHISITERATION(i: *mut c_int, Value: *mut c_double) {
+    with_mock_kernel(|kernel| {
+        kernel.set(
+            "SETDYNAMICARRAYVALUETHISITERATION",
+            "dynamic storage",
+            *i,
+            *Value,
+            |k| &mut k.dynamic_storage_this_iteration,
+        )
+    })
+}
+
+#[unsafe(export_name = kernel_symbol!(SETINPUTUNITS))]
+pub unsafe extern "C" fn SETINPUTUNITS(i: *mut c_int, String: *mut c_char, len: usize) {
+    let unit = read_string(String, len);
+    with_mock_kernel(|kernel| kernel.declared.input_units.insert(*i, unit));
+}
+
+#[unsafe(export_name = kernel_symbol!(SETITERATIONMODE))]
+pub unsafe extern "C" fn SETITERATIONMODE(i: *mut c_int) {
+    with_mock_kernel(|kernel| kernel.declared.iteration_mode = Some(*i))
+}
+
+#[unsafe(export_name = kernel_symbol!(SETNUMBEROFDERIVATIVES))]
+pub unsafe extern "C" fn SETNUMBEROFDERIVATIVES(i: *mut c_int) {
+    with_mock_kernel(|kernel| {
+        kernel.declared.derivatives = Some(*i);
+        kernel
+            .numerical_derivatives
+            .resize((*i).max(0) as usize, 0.);
+    })
+}
+
+#[unsafe(export_name = kernel_symbol!(SETNUMBEROFDISCRETECONTROLS))]
+pub unsafe extern "C" fn SETNUMBEROFDISCRETECONTROLS(i: *mut c_int) {
+    with_mock_kernel(|kernel| {
+        kernel.declared.discrete_controls = Some(*i);
+        kernel
+            .desired_control_states
+            .resize((*i).max(0) as usize, 0);
+    })
+}
+
+#[unsafe(export_name = kernel_symbol!(SETNUMBEROFINPUTS))]
+pub unsafe extern "C" fn SETNUMBEROFINPUTS(i: *mut c_int) {
+    with_mock_kernel(|kernel| kernel.declared.inputs = Some(*i))
+}
+
+#[unsafe(export_name = kernel_symbol!(SETNUMBEROFOUTPUTS))]
+pub unsafe extern "C" fn SETNUMBEROFOUTPUTS(i: *mut c_int) {
+    with_mock_kernel(|kernel| {
+        kernel.declared.outputs = Some(*i);
+        kernel.outputs.resize((*i).max(0) as usize, 0.);
+    })
+}
+
+#[unsafe(export_name = kernel_symbol!(SETNUMBEROFPARAMETERS))]
+pub unsafe extern "C" fn SETNUMBEROFPARAMETERS(i: *mut c_int) {
+    with_mock_kernel(|kernel| kernel.declared.params = Some(*i))
+}
+
+#[unsafe(export_name = kernel_symbol!(SETNUMBERSTOREDVARIABLES))]
+pub unsafe extern "C" fn SETNUMBERSTOREDVARIABLES(
+    Nrequested_Static: *mut c_int,
+    Nrequested_Dynamic: *mut c_int,
+) {
+    with_mock_kernel(|kernel| {
+        kernel.declared.static_storage = Some(*Nrequested_Static);
+        kernel.declared.dynamic_storage = Some(*Nrequested_Dynamic);
+        let dynamic = (*Nrequested_Dynamic).max(0) as usize;
+        kernel
+            .static_storage
+            .resize((*Nrequested_Static).max(0) as usize, 0.);
+        kernel.dynamic_storage.resize(dynamic, 0.);
+        kernel.dynamic_storage_this_iteration.resize(dynamic, 0.);
+    })
+}
+
+#[unsafe(export_name = kernel_symbol!(SETNUMERICALDERIVATIVE))]
+pub unsafe extern "C" fn SETNUMERICALDERIVATIVE(i: *mut c_int, Value: *mut c_double) {
+    with_mock_kernel(|kernel| {
+        kernel.set("SETNUMERICALDERIVATIVE", "derivative", *i, *Value, |k| {
+            &mut k.numerical_derivatives
+        })
+    })
+}
+
+#[unsafe(export_name = kernel_symbol!(SETOUTPUTUNITS))]
+pub unsafe extern "C" fn SETOUTPUTUNITS(i: *mut c_int, String: *mut c_char, len: usize) {
+    let unit = read_string(String, len);
+    with_mock_kernel(|kernel| kernel.declared.output_units.insert(*i, unit));
+}
+
+#[unsafe(export_name = kernel_symbol!(SETOUTPUTVALUE))]
+pub unsafe extern "C" fn SETOUTPUTVALUE(i: *mut c_int, Value: *mut c_double) {
+    with_mock_kernel(|kernel| {
+        kernel.set("SETOUTPUTVALUE", "output", *i, *Value, |k| &mut k.outputs)
+    })
+}
+
+#[unsafe(export_name = kernel_symbol!(SETSTATICARRAYVALUE))]
+pub unsafe extern "C" fn SETSTATICARRAYVALUE(i: *mut c_int, Value: *mut c_double) {
+    with_mock_kernel(|kernel| {
+        kernel.set("SETSTATICARRAYVALUE", "static storage", *i, *Value, |k| {
+            &mut k.static_storage
+        })
+    })
+}
+
+#[unsafe(export_name = kernel_symbol!(SETTYPEVERSION))]
+pub unsafe extern "C" fn SETTYPEVERSION(i: *mut c_int) -> c_int {
+    with_mock_kernel(|kernel| kernel.declared.type_version = Some(*i));
+    0
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_ERRORFOUND))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_ERRORFOUND() -> c_int {
+    with_mock_kernel(|kernel| flag(kernel.error_found))
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETCONVERGENCETOLERANCE))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETCONVERGENCETOLERANCE() -> c_double {
+    with_mock_kernel(|kernel| kernel.convergence_tolerance)
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETCURRENTTYPE))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETCURRENTTYPE() -> c_int {
+    with_mock_kernel(|kernel| kernel.type_number)
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETCURRENTUNIT))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETCURRENTUNIT() -> c_int {
+    with_mock_kernel(|kernel| kernel.unit)
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETDECKFILENAME))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETDECKFILENAME(
+    dck: *mut c_char,
+    len: usize,
+) -> *mut c_char {
+    with_mock_kernel(|kernel| write_string(dck, len, &kernel.deck_file))
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETDYNAMICARRAYVALUELASTTIMESTEP))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETDYNAMICARRAYVALUELASTTIMESTEP(
+    i: *mut c_int,
+) -> c_double {
+    with_mock_kernel(|kernel| {
+        kernel.get(
+            "GETDYNAMICARRAYVALUELASTTIMESTEP",
+            "dynamic storage",
+            *i,
+            |k| &k.dynamic_storage,
+        )
+    })
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETFORMAT))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETFORMAT(
+    label: *mut c_char,
+    llen: usize,
+    iunit: *mut c_int,
+    no: *mut c_int,
+) -> *mut c_char {
+    with_mock_kernel(|kernel| write_string(label, llen, nth(&kernel.formats, *no)))
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETINPUTVALUE))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETINPUTVALUE(i: *mut c_int) -> c_double {
+    with_mock_kernel(|kernel| kernel.get("GETINPUTVALUE", "input", *i, |k| &k.inputs))
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETISENDOFTIMESTEP))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETISENDOFTIMESTEP() -> c_int {
+    with_mock_kernel(|kernel| flag(kernel.phase == CallPhase::EndOfTimestep))
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETISFIRSTCALLOFSIMULATION))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETISFIRSTCALLOFSIMULATION() -> c_int {
+    with_mock_kernel(|kernel| flag(kernel.phase == CallPhase::FirstCall))
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETISLASTCALLOFSIMULATION))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETISLASTCALLOFSIMULATION() -> c_int {
+    with_mock_kernel(|kernel| flag(kernel.phase == CallPhase::LastCall))
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETISREREADPARAMETERS))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETISREREADPARAMETERS() -> c_int {
+    with_mock_kernel(|kernel| flag(kernel.phase == CallPhase::RereadParameters))
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETISSTARTTIME))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETISSTARTTIME() -> c_int {
+    with_mock_kernel(|kernel| flag(kernel.phase == CallPhase::StartTime))
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETISVERSIONSIGNINGTIME))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETISVERSIONSIGNINGTIME() -> c_int {
+    with_mock_kernel(|kernel| flag(kernel.phase == CallPhase::VersionSigning))
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETLABEL))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETLABEL(
+    label: *mut c_char,
+    llen: usize,
+    iunit: *mut c_int,
+    no: *mut c_int,
+) -> *mut c_char {
+    with_mock_kernel(|kernel| write_string(label, llen, nth(&kernel.labels, *no)))
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETLUFILENAME))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETLUFILENAME(
+    name: *mut c_char,
+    llen: usize,
+    lu: *mut c_int,
+) -> *mut c_char {
+    with_mock_kernel(|kernel| {
+        let file = kernel
+            .logical_unit_files
+            .get(&*lu)
+            .map_or("", String::as_str);
+        write_string(name, llen, file)
+    })
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETMAXDESCRIPLENGTH))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETMAXDESCRIPLENGTH() -> c_int {
+    with_mock_kernel(|kernel| kernel.max_descrip_length)
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETMAXLABELLENGTH))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETMAXLABELLENGTH() -> c_int {
+    with_mock_kernel(|kernel| kernel.max_label_length)
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETMAXPATHLENGTH))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETMAXPATHLENGTH() -> c_int {
+    with_mock_kernel(|kernel| kernel.max_path_length)
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETMINIMUMTIMESTEP))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETMINIMUMTIMESTEP() -> c_double {
+    with_mock_kernel(|kernel| kernel.minimum_timestep)
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETNEXTAVAILABLELOGICALUNIT))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETNEXTAVAILABLELOGICALUNIT() -> c_int {
+    with_mock_kernel(|kernel| {
+        while kernel
+            .logical_unit_files
+            .contains_key(&kernel.next_logical_unit)
+        {
+            kernel.next_logical_unit += 1;
+        }
+        kernel.next_logical_unit += 1;
+        kernel.next_logical_unit - 1
+    })
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETNUMBEROFDERIVATIVES))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETNUMBEROFDERIVATIVES() -> c_int {
+    with_mock_kernel(|kernel| kernel.num_derivatives)
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETNUMBEROFINPUTS))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETNUMBEROFINPUTS() -> c_int {
+    with_mock_kernel(|kernel| kernel.inputs.len() as c_int)
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETNUMBEROFLABELS))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETNUMBEROFLABELS(i: *mut c_int) -> c_int {
+    with_mock_kernel(|kernel| kernel.labels.len() as c_int)
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETNUMBEROFOUTPUTS))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETNUMBEROFOUTPUTS() -> c_int {
+    with_mock_kernel(|kernel| kernel.num_outputs)
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETNUMBEROFPARAMETERS))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETNUMBEROFPARAMETERS() -> c_int {
+    with_mock_kernel(|kernel| kernel.params.len() as c_int)
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETNUMERICALSOLUTION))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETNUMERICALSOLUTION(i: *mut c_int) -> c_double {
+    with_mock_kernel(|kernel| {
+        kernel.get("GETNUMERICALSOLUTION", "numerical solution", *i, |k| {
+            &k.numerical_solution
+        })
+    })
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETOUTPUTVALUE))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETOUTPUTVALUE(i: *mut c_int) -> c_double {
+    with_mock_kernel(|kernel| kernel.get("GETOUTPUTVALUE", "output", *i, |k| &k.outputs))
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETPARAMETERVALUE))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETPARAMETERVALUE(i: *mut c_int) -> c_double {
+    with_mock_kernel(|kernel| kernel.get("GETPARAMETERVALUE", "parameter", *i, |k| &k.params))
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETPREVIOUSCONTROLSTATE))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETPREVIOUSCONTROLSTATE(i: *mut c_int) -> c_int {
+    with_mock_kernel(|kernel| {
+        kernel.get("GETPREVIOUSCONTROLSTATE", "control", *i, |k| {
+            &k.previous_control_states
+        })
+    })
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETSIMULATIONSTARTTIME))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETSIMULATIONSTARTTIME() -> c_double {
+    with_mock_kernel(|kernel| kernel.start_time)
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETSIMULATIONSTOPTIME))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETSIMULATIONSTOPTIME() -> c_double {
+    with_mock_kernel(|kernel| kernel.stop_time)
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETSIMULATIONTIME))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETSIMULATIONTIME() -> c_double {
+    with_mock_kernel(|kernel| kernel.time)
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETSIMULATIONTIMESTEP))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETSIMULATIONTIMESTEP() -> c_double {
+    with_mock_kernel(|kernel| kernel.timestep)
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETSTATICARRAYVALUE))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETSTATICARRAYVALUE(i: *mut c_int) -> c_double {
+    with_mock_kernel(|kernel| {
+        kernel.get("GETSTATICARRAYVALUE", "static storage", *i, |k| {
+            &k.static_storage
+        })
+    })
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETTIMESTEPITERATION))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETTIMESTEPITERATION() -> c_int {
+    with_mock_kernel(|kernel| kernel.iteration)
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETTRNSYSINPUTFILEDIR))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETTRNSYSINPUTFILEDIR(
+    dir: *mut c_char,
+    len: usize,
+) -> *mut c_char {
+    with_mock_kernel(|kernel| write_string(dir, len, &kernel.input_file_dir))
+}
+
+#[unsafe(export_name = kernel_symbol!(TRNSYSFUNCTIONS_mp_GETTRNSYSROOTDIR))]
+pub unsafe extern "C" fn TRNSYSFUNCTIONS_mp_GETTRNSYSROOTDIR(
+    dir: *mut c_char,
+    len: usize,
+) -> *mut c_char {
+    with_mock_kernel(|kernel| write_string(dir, len, &kernel.root_dir))
+}
+
+// --- TRNSYS subroutines ----------------------------------------------------------------------------
+
+#[unsafe(export_name = kernel_symbol!(FLUID_PROPERTIES))]
+pub unsafe extern "C" fn FLUID_PROPERTIES(
+    units: *mut c_char,
+    prop: *mut c_double,
+    nref: *mut c_int,
+    itype: *mut c_int,
+    iflagr: *mut c_int,
+    len: usize,
+) {
+    with_mock_kernel(|kernel| kernel.unavailable("FLUID_PROPERTIES"))
+}
+
+#[unsafe(export_name = kernel_symbol!(GETHORIZONTALRADIATION))]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn GETHORIZONTALRADIATION(
+    Time: *mut c_double,
+    mode_rad: *mut c_int,
+    mode_shape: *mut c_int,
+    rad_input: *mut c_double,
+    rhog: *mut c_double,
+    slope: *mut c_double,
+    azimuth: *mut c_double,
+    mode_track: *mut c_int,
+    mode_tilt: *mut c_int,
+    latitude: *mut c_double,
+    alt: *mut c_double,
+    shift: *mut c_double,
+    i_solartime: *mut c_int,
+    SolConst: *mut c_double,
+    td1: *mut c_double,
+    td2: *mut c_double,
+    solar: *mut c_double,
+    ierror_rad: *mut c_int,
+) {
+    *ierror_rad = 1;
+    with_mock_kernel(|kernel| kernel.unavailable("GETHORIZONTALRADIATION"))
+}
+
+#[unsafe(export_name = kernel_symbol!(GETTILTEDRADIATION))]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn GETTILTEDRADIATION(
+    Time: *mut c_double,
+    rhog: *mut c_double,
+    slope: *mut c_double,
+    azimuth: *mut c_double,
+    mode_track: *mut c_int,
+    mode_tilt: *mut c_int,
+    alt: *mut c_double,
+    SolConst: *mut c_double,
+    solar: *mut c_double,
+    ierror_rad: *mut c_int,
+) {
+    *ierror_rad = 1;
+    with_mock_kernel(|kernel| kernel.unavailable("GETTILTEDRADIATION"))
+}
+
+#[unsafe(export_name = kernel_symbol!(INTERPOLATEDATA))]
+pub unsafe extern "C" fn INTERPOLATEDATA(
+    LUdd: *mut c_int,
+    NINDdd: *mut c_int,
+    NXdd: *mut c_int,
+    NYdd: *mut c_int,
+    Xdd: *mut c_double,
+    Ydd: *mut c_double,
+) {
+    with_mock_kernel(|kernel| kernel.unavailable("INTERPOLATEDATA"))
+}
+
+#[unsafe(export_name = kernel_symbol!(MESSAGES))]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn MESSAGES(
+    errorCode: *mut c_int,
+    message: *mut c_char,
+    severity: *mut c_char,
+    unitNo: *mut c_int,
+    typeNo: *mut c_int,
+    n: usize,
+    m: usize,
+) {
+    let message = MockMessage {
+        code: *errorCode,
+        severity: read_string(severity, m),
+        message: read_string(message, n),
+        unit: *unitNo,
+        type_number: *typeNo,
+    };
+    with_mock_kernel(|kernel| {
+        kernel.error_found |= is_error(&message.severity);
+        kernel.messages.push(message);
+    })
+}
+
+#[unsafe(export_name = kernel_symbol!(MOISTAIRPROPERTIES))]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn MOISTAIRPROPERTIES(
+    CurUnit: *mut c_int,
+    CurType: *mut c_int,
+    iunits: *mut c_int,
+    mode: *mut c_int,
+    wbmd: *mut c_int,
+    psydat: *mut c_double,
+    emode: *mut c_int,
+    status: *mut c_int,
+) {
+    *status = 1;
+    with_mock_kernel(|kernel| kernel.unavailable("MOISTAIRPROPERTIES"))
+}
+
+/// Solves `dT/dt = aa T + bb` over the time step, from `Ti` to `Tf`, with the average `Tbar`.
+#[unsafe(export_name = kernel_symbol!(SOLVEDIFFEQ))]
+pub unsafe extern "C" fn SOLVEDIFFEQ(
+    aa: *mut c_double,
+    bb: *mut c_double,
+    Ti: *mut c_double,
+    Tf: *mut c_double,
+    Tbar: *mut c_double,
+) {
+    let (a, b, initial) = (*aa, *bb, *Ti);
+    let dt = with_mock_kernel(|kernel| kernel.timestep);
+    if a == 0. {
+        *Tf = initial + b * dt;
+        *Tbar = initial + b * dt / 2.;
+    } else {
+        let steady = -b / a;
+        let growth = (a * dt).exp();
+        *Tf = steady + (initial - steady) * growth;
+        *Tbar = steady + (initial - steady) * (growth - 1.) / (a * dt);
+    }
+}
+
+#[unsafe(export_name = kernel_symbol!(STEAM_PROPERTIES))]
+pub unsafe extern "C" fn STEAM_PROPERTIES(
+    units: *mut c_char,
+    prop: *mut c_double,
+    itype: *mut c_int,
+    ierrst: *mut c_int,
+    len: usize,
+) {
+    *ierrst = 1;
+    with_mock_kernel(|kernel| kernel.unavailable("STEAM_PROPERTIES"))
+}
@@ -1,5 +1,6 @@
 #![allow(unused)]
 
+use crate::trnsys::error::TrnSysError;
 pub use ext_c::FLUID_PROPERTIES as fluid_properties;
 pub use ext_c::GETHORIZONTALRADIATION as get_horizontal_radiation;
 pub use ext_c::GETTILTEDRADIATION as get_tilted_radiation;
@@ -8,25 +9,77 @@ pub use ext_c::MESSAGES as messages;
 pub use ext_c::MOISTAIRPROPERTIES as moist_air_properties;
 pub use ext_c::SOLVEDIFFEQ as solve_diff_eq;
 pub use ext_c::STEAM_PROPERTIES as steam_properties;
+use fortran_string::{FortranBuffer, FortranString};
 pub use fpu_guard::fpu_guard::FpuGuard;
-use std::ffi::{CStr, CString};
+use index::{check_phase, declared_counts, OUTPUT_PHASES, PARAMETER_PHASES, RUNNING_PHASES};
 use std::os::raw::{c_char, c_double, c_int};
 use tracing::info;
+use unit_catalog::TrnSysUnit;
 use util::c_bool;
 
+pub(crate) mod call_context;
+pub(crate) mod convergence;
+pub(crate) mod dimensions;
 pub mod error;
 mod ext_c;
+mod fortran_string;
 mod fpu_guard;
+pub(crate) mod index;
+pub(crate) mod input_check;
 pub(super) mod iteration_mode;
+pub mod kernel_version;
+mod json_lines;
+pub(crate) mod list_file_limiter;
+pub mod log_config;
+mod log_retention;
 pub mod logging;
+#[cfg(mock_kernel)]
+pub mod mock_kernel;
+pub(crate) mod output_cache;
+pub(crate) mod output_guard;
 pub(super) mod param;
+pub(crate) mod quantity;
 mod state;
+pub(crate) mod unit_catalog;
 mod util;
 
+pub use call_context::*;
+pub use index::{InputIndex, OutputIndex, ParamIndex, StorageIndex};
 pub use state::*;
 
 // This file declares all the global functions available to C / C++ TRNSYS Types
 
+/// Looks up a kernel function declared as a type in `ext_c`, which not every kernel exports. The
+/// function is looked up once, at the first call.
+macro_rules! kernel_function {
+    ($name:ident) => {{
+        const NAME: &std::ffi::CStr = match std::ffi::CStr::from_bytes_with_nul(
+            concat!(kernel_symbol!($name), "\0").as_bytes(),
+        ) {
+            Ok(name) => name,
+            Err(_) => panic!("symbol without NUL"),
+        };
+        static FUNCTION: std::sync::OnceLock<Option<ext_c::$name>> = std::sync::OnceLock::new();
+        *FUNCTION.get_or_init(|| unsafe { kernel_version::kernel_function::<ext_c::$name>(NAME) })
+    }};
+}
+
+/// The maximum length of a message written to the list file, `maxMessageLength` in the
+/// `TrnsysConstants` module of the kernel.
+pub const MAX_MESSAGE_LENGTH: usize = 800;
+
+/// The maximum length of a message written to the list file, from `getMaxMessageLength` if the
+/// kernel exports it, [`MAX_MESSAGE_LENGTH`] otherwise.
+pub fn max_message_length() -> usize {
+    let Some(function) = kernel_function!(TRNSYSFUNCTIONS_mp_GETMAXMESSAGELENGTH) else {
+        return MAX_MESSAGE_LENGTH;
+    };
+    match usize::try_from(unsafe { function() }) {
+        Ok(len) if len > 0 => len,
+        _ => MAX_MESSAGE_LENGTH,
+    }
+}
+
 pub(crate) enum Severity {
     Notice,
     Warning,
@@ -35,12 +88,12 @@ pub(crate) enum Severity {
 }
 
 impl Severity {
-    fn as_cstring(&self) -> CString {
+    fn as_fortran(&self) -> FortranString {
         match self {
-            Severity::Notice => CString::new("notice").unwrap(),
-            Severity::Warning => CString::new("Warning").unwrap(),
-            Severity::Fatal => CString::new("FATAL").unwrap(),
-            Severity::Stop => CString::new("STOP").unwrap(),
+            Severity::Notice => FortranString::new("notice"),
+            Severity::Warning => FortranString::new("Warning"),
+            Severity::Fatal => FortranString::new("FATAL"),
+            Severity::Stop => FortranString::new("STOP"),
         }
     }
 }
@@ -49,125 +102,139 @@ impl Severity {
 ///
 /// # Arguments
 ///
-/// * `input` - A mutable reference to the input index (0-indexed).
+/// * `input` - The index of the bad input.
 /// * `severity` - The severity of the error.
-/// * `message` - A message describing the error.
+/// * `message` - A message describing the error, truncated to [`get_max_descrip_length`].
 ///
 /// # Safety
 ///
 /// This function uses unsafe code to interact with the TRNSYS engine.
-pub fn found_bad_input(mut input: i32, severity: Severity, message: &str) {
+pub fn found_bad_input(
+    input: InputIndex,
+    severity: Severity,
+    message: &str,
+) -> Result<(), TrnSysError> {
+    let mut input = input.to_fortran(declared_counts("found_bad_input")?.inputs)?;
+    let mut severity = severity.as_fortran();
+    let max_len = get_max_descrip_length().max(0) as usize;
+    let mut message = FortranString::with_max_len(message, max_len);
     unsafe {
-        input += 1;
-        let severity = severity.as_cstring();
-        let message = CString::new(message).unwrap();
         ext_c::FOUNDBADINPUT(
-            input as *mut c_int,
-            severity.as_ptr() as *mut c_char,
-            message.as_ptr() as *mut c_char,
-            severity.as_bytes().len(),
-            message.as_bytes().len(),
+            &mut input as *mut c_int,
+            severity.as_mut_ptr(),
+            message.as_mut_ptr(),
+            severity.len(),
+            message.len(),
         );
     }
+    Ok(())
 }
 
 /// Reports a bad parameter to the TRNSYS engine.
 ///
 /// # Arguments
 ///
-/// * `param` - A mutable reference to the parameter index (0-indexed).
+/// * `param` - The index of the bad parameter.
 /// * `severity` - The severity of the error.
-/// * `message` - A message describing the error.
+/// * `message` - A message describing the error, truncated to [`get_max_descrip_length`].
 ///
 /// # Safety
 ///
 /// This function uses unsafe code to interact with the TRNSYS engine.
-pub fn found_bad_parameter(mut param: i32, severity: Severity, message: &str) {
+pub fn found_bad_parameter(
+    param: ParamIndex,
+    severity: Severity,
+    message: &str,
+) -> Result<(), TrnSysError> {
     info!("Found bad parameter");
+    let mut param: c_int = param.to_fortran(declared_counts("found_bad_parameter")?.params)?;
+    let mut severity = severity.as_fortran();
+    let max_len = get_max_descrip_length().max(0) as usize;
+    let mut message = FortranString::with_max_len(message, max_len);
     unsafe {
-        param += 1;
-
-        let mut param: c_int = param;
-        let severity = severity.as_cstring();
-
-        let severity_ptr = severity.as_ptr() as *mut c_char;
-        let severity_len = severity.as_bytes().len();
-
-        let message = CString::new(message).expect("Failed to create CString");
-
-        let message_ptr = message.as_ptr() as *mut c_char;
-        let message_len = message.as_bytes().len();
-
-        info!(
-            "Param ptr: {:p}, Severity ptr: {:p}, Message ptr: {:p}",
-            &mut param as *mut c_int, severity_ptr, message_ptr
-        );
-
-        let message = CString::new(message).unwrap();
         ext_c::FOUNDBADPARAMETER(
             &mut param as *mut c_int,
-            severity_ptr,
-            message_ptr,
-            severity_len,
-            message_len,
+            severity.as_mut_ptr(),
+            message.as_mut_ptr(),
+            severity.len(),
+            message.len(),
         );
     }
+    Ok(())
 }
+/// The functions of the simulation summary report do nothing in kernels without it, see
+/// [`kernel_version::KernelFeature`].
 pub fn init_report_integral(index: &mut i32, int_name: &str, inst_unit: &str, int_unit: &str) {
+    let Some(function) = kernel_function!(INITREPORTINTEGRAL) else {
+        return;
+    };
+    let max_len = get_max_descrip_length().max(0) as usize;
+    let mut int_name = FortranString::with_max_len(int_name, max_len);
+    let mut inst_unit = FortranString::with_max_len(inst_unit, max_len);
+    let mut int_unit = FortranString::with_max_len(int_unit, max_len);
     unsafe {
-        let cstr_int_name = CString::new(int_name).unwrap();
-        let cstr_inst_unit = CString::new(inst_unit).unwrap();
-        let cstr_int_unit = CString::new(int_unit).unwrap();
-        ext_c::INITREPORTINTEGRAL(
+        function(
             index,
-            cstr_int_name.as_ptr() as *mut c_char,
-            cstr_inst_unit.as_ptr() as *mut c_char,
-            cstr_int_unit.as_ptr() as *mut c_char,
-            cstr_int_name.as_bytes().len(),
-            cstr_inst_unit.as_bytes().len(),
-            cstr_int_unit.as_bytes().len(),
+            int_name.as_mut_ptr(),
+            inst_unit.as_mut_ptr(),
+            int_unit.as_mut_ptr(),
+            int_name.len(),
+            inst_unit.len(),
+            int_unit.len(),
         );
     }
 }
 
 pub fn init_report_min_max(index: &mut i32, minmax_name: &str, minmax_unit: &str) {
+    let Some(function) = kernel_function!(INITREPORTMINMAX) else {
+        return;
+    };
+    let max_len = get_max_descrip_length().max(0) as usize;
+    let mut minmax_name = FortranString::with_max_len(minmax_name, max_len);
+    let mut minmax_unit = FortranString::with_max_len(minmax_unit, max_len);
     unsafe {
-        let cstr_minmax_name = CString::new(minmax_name).unwrap();
-        let cstr_minmax_unit = CString::new(minmax_unit).unwrap();
-        ext_c::INITREPORTMINMAX(
+        function(
             index,
-            cstr_minmax_name.as_ptr() as *mut c_char,
-            cstr_minmax_unit.as_ptr() as *mut c_char,
-            cstr_minmax_name.as_bytes().len(),
-            cstr_minmax_unit.as_bytes().len(),
+            minmax_name.as_mut_ptr(),
+            minmax_unit.as_mut_ptr(),
+            minmax_name.len(),
+            minmax_unit.len(),
         );
     }
 }
 
 pub fn init_report_text(index: &mut i32, txt_name: &str, txt_val: &str) {
+    let Some(function) = kernel_function!(INITREPORTTEXT) else {
+        return;
+    };
+    let max_len = get_max_descrip_length().max(0) as usize;
+    let mut txt_name = FortranString::with_max_len(txt_name, max_len);
+    let mut txt_val = FortranString::with_max_len(txt_val, max_len);
     unsafe {
-        let cstr_txt_name = CString::new(txt_name).unwrap();
-        let cstr_txt_val = CString::new(txt_val).unwrap();
-        ext_c::INITREPORTTEXT(
+        function(
             index,
-            cstr_txt_name.as_ptr() as *mut c_char,
-            cstr_txt_val.as_ptr() as *mut c_char,
-            cstr_txt_name.as_bytes().len(),
-            cstr_txt_val.as_bytes().len(),
+            txt_name.as_mut_ptr(),
+            txt_val.as_mut_ptr(),
+            txt_name.len(),
+            txt_val.len(),
         );
     }
 }
 
 pub fn init_report_value(index: &mut i32, val_name: &str, val_val: &f64, val_unit: &str) {
+    let Some(function) = kernel_function!(INITREPORTVALUE) else {
+        return;
+    };
+    let max_len = get_max_descrip_length().max(0) as usize;
+    let mut val_name = FortranString::with_max_len(val_name, max_len);
+    let mut val_unit = FortranString::with_max_len(val_unit, max_len);
+    let mut val_val = *val_val as c_double;
     unsafe {
-        let cstr_val_name = CString::new(val_name).unwrap();
-        let cstr_val_unit = CString::new(val_unit).unwrap();
-        let mut val_val = *val_val as c_double;
-        ext_c::INITREPORTVALUE(
+        function(
             index,
-            cstr_val_name.as_ptr() as *mut c_char,
+            val_name.as_mut_ptr(),
             &mut val_val,
-            cstr_val_unit.as_ptr() as *mut c_char,
+            val_unit.as_mut_ptr(),
             val_name.len(),
             val_unit.len(),
         );
@@ -182,44 +249,68 @@ pub fn set_desired_discrete_control_state(mut i: i32, mut j: i32) {
     unsafe { ext_c::SETDESIREDDISCRETECONTROLSTATE(&mut i, &mut j) }
 }
 
-pub fn set_dynamic_array_initial_value(mut i: i32, mut value: f64) {
+pub fn set_dynamic_array_initial_value(i: StorageIndex, mut value: f64) -> Result<(), TrnSysError> {
+    const NAME: &str = "set_dynamic_array_initial_value";
+    check_phase(NAME, &[CallPhase::StartTime])?;
+    let mut i = i.to_fortran(declared_counts(NAME)?.dynamic_storage)?;
     unsafe { ext_c::SETDYNAMICARRAYINITIALVALUE(&mut i, &mut value) }
+    Ok(())
 }
 
-pub fn set_dynamic_array_value_this_iteration(mut i: i32, mut value: f64) {
+pub fn set_dynamic_array_value_this_iteration(
+    i: StorageIndex,
+    mut value: f64,
+) -> Result<(), TrnSysError> {
+    const NAME: &str = "set_dynamic_array_value_this_iteration";
+    check_phase(NAME, OUTPUT_PHASES)?;
+    let mut i = i.to_fortran(declared_counts(NAME)?.dynamic_storage)?;
     unsafe { ext_c::SETDYNAMICARRAYVALUETHISITERATION(&mut i, &mut value) }
+    Ok(())
 }
 
-pub fn set_input_units(mut i: i32, string: &str) {
-    i += 1;
-    unsafe {
-        let cstr = CString::new(string).unwrap();
-        ext_c::SETINPUTUNITS(&mut i, cstr.as_ptr() as *mut c_char, string.len());
-    }
+pub fn set_input_units(i: InputIndex, unit: TrnSysUnit) -> Result<(), TrnSysError> {
+    const NAME: &str = "set_input_units";
+    check_phase(NAME, &[CallPhase::FirstCall])?;
+    let mut i = i.to_fortran(declared_counts(NAME)?.inputs)?;
+    let mut string = FortranString::new(unit.code());
+    unsafe { ext_c::SETINPUTUNITS(&mut i, string.as_mut_ptr(), string.len()) }
+    Ok(())
 }
 
-pub fn set_iteration_mode(mut i: i32) {
+pub fn set_iteration_mode(mut i: i32) -> Result<(), TrnSysError> {
+    check_phase("set_iteration_mode", &[CallPhase::FirstCall])?;
     unsafe { ext_c::SETITERATIONMODE(&mut i) }
+    Ok(())
 }
 
-pub fn set_number_of_derivatives(mut i: i32) {
+pub fn set_number_of_derivatives(mut i: i32) -> Result<(), TrnSysError> {
+    check_phase("set_number_of_derivatives", &[CallPhase::FirstCall])?;
     unsafe { ext_c::SETNUMBEROFDERIVATIVES(&mut i) }
+    Ok(())
 }
 
-pub fn set_number_of_discrete_controls(mut i: i32) {
+pub fn set_number_of_discrete_controls(mut i: i32) -> Result<(), TrnSysError> {
+    check_phase("set_number_of_discrete_controls", &[CallPhase::FirstCall])?;
     unsafe { ext_c::SETNUMBEROFDISCRETECONTROLS(&mut i) }
+    Ok(())
 }
 
-pub fn set_number_of_inputs(mut i: i32) {
+pub fn set_number_of_inputs(mut i: i32) -> Result<(), TrnSysError> {
+    check_phase("set_number_of_inputs", &[CallPhase::FirstCall])?;
     unsafe { ext_c::SETNUMBEROFINPUTS(&mut i) }
+    Ok(())
 }
 
-pub fn set_number_of_outputs(mut i: i32) {
+pub fn set_number_of_outputs(mut i: i32) -> Result<(), TrnSysError> {
+    check_phase("set_number_of_outputs", &[CallPhase::FirstCall])?;
     unsafe { ext_c::SETNUMBEROFOUTPUTS(&mut i) }
+    Ok(())
 }
 
-pub fn set_number_of_parameters(mut i: i32) {
+pub fn set_number_of_parameters(mut i: i32) -> Result<(), TrnSysError> {
+    check_phase("set_number_of_parameters", &[CallPhase::FirstCall])?;
     unsafe { ext_c::SETNUMBEROFPARAMETERS(&mut i) }
+    Ok(())
 }
 
 pub fn set_number_of_report_variables(
@@ -228,37 +319,52 @@ pub fn set_number_of_report_variables(
     mut n_vals: i32,
     mut n_text: i32,
 ) {
-    unsafe {
-        ext_c::SETNUMBEROFREPORTVARIABLES(&mut n_int, &mut n_min_max, &mut n_vals, &mut n_text)
+    if let Some(function) = kernel_function!(SETNUMBEROFREPORTVARIABLES) {
+        unsafe { function(&mut n_int, &mut n_min_max, &mut n_vals, &mut n_text) }
     }
 }
 
-pub fn set_number_stored_variables(mut n_requested_static: i32, mut n_requested_dynamic: i32) {
+pub fn set_number_stored_variables(
+    mut n_requested_static: i32,
+    mut n_requested_dynamic: i32,
+) -> Result<(), TrnSysError> {
+    check_phase("set_number_stored_variables", &[CallPhase::FirstCall])?;
     unsafe { ext_c::SETNUMBERSTOREDVARIABLES(&mut n_requested_static, &mut n_requested_dynamic) }
+    Ok(())
 }
 
 pub fn set_numerical_derivative(mut i: i32, mut value: f64) {
     unsafe { ext_c::SETNUMERICALDERIVATIVE(&mut i, &mut value) }
 }
 
-pub fn set_output_units(mut i: i32, string: &str) {
-    unsafe {
-        let cstr = CString::new(string).unwrap();
-        ext_c::SETOUTPUTUNITS(&mut i, cstr.as_ptr() as *mut c_char, string.len());
-    }
+pub fn set_output_units(i: OutputIndex, unit: TrnSysUnit) -> Result<(), TrnSysError> {
+    const NAME: &str = "set_output_units";
+    check_phase(NAME, &[CallPhase::FirstCall])?;
+    let mut i = i.to_fortran(declared_counts(NAME)?.outputs)?;
+    let mut string = FortranString::new(unit.code());
+    unsafe { ext_c::SETOUTPUTUNITS(&mut i, string.as_mut_ptr(), string.len()) }
+    Ok(())
 }
 
-pub fn set_output_value(mut i: i32, mut value: f64) {
-    i += 1;
+pub fn set_output_value(i: OutputIndex, mut value: f64) -> Result<(), TrnSysError> {
+    const NAME: &str = "set_output_value";
+    check_phase(NAME, OUTPUT_PHASES)?;
+    let mut i = i.to_fortran(declared_counts(NAME)?.outputs)?;
     unsafe { ext_c::SETOUTPUTVALUE(&mut i, &mut value) }
+    Ok(())
 }
 
-pub fn set_static_array_value(mut i: i32, mut value: f64) {
+pub fn set_static_array_value(i: StorageIndex, mut value: f64) -> Result<(), TrnSysError> {
+    const NAME: &str = "set_static_array_value";
+    check_phase(NAME, RUNNING_PHASES)?;
+    let mut i = i.to_fortran(declared_counts(NAME)?.static_storage)?;
     unsafe { ext_c::SETSTATICARRAYVALUE(&mut i, &mut value) }
+    Ok(())
 }
 
-pub fn set_type_version(mut i: i32) -> i32 {
-    unsafe { ext_c::SETTYPEVERSION(&mut i) }
+pub fn set_type_version(mut i: i32) -> Result<i32, TrnSysError> {
+    check_phase("set_type_version", &[CallPhase::VersionSigning])?;
+    Ok(unsafe { ext_c::SETTYPEVERSION(&mut i) })
 }
 
 pub fn error_found() -> bool {
@@ -278,42 +384,32 @@ pub fn get_current_unit() -> i32 {
 }
 
 pub fn get_deck_filename() -> String {
-    let mut buffer = vec![0 as c_char; get_max_path_length() as usize];
-    unsafe {
-        let ptr = ext_c::TRNSYSFUNCTIONS_mp_GETDECKFILENAME(
-            buffer.as_mut_slice().as_mut_ptr(),
-            buffer.len(),
-        );
-        CStr::from_ptr(ptr).to_string_lossy().into_owned()
-    }
+    let mut buffer = FortranBuffer::new(get_max_path_length());
+    unsafe { ext_c::TRNSYSFUNCTIONS_mp_GETDECKFILENAME(buffer.as_mut_ptr(), buffer.len()) };
+    buffer.to_string_lossy()
 }
 
-pub fn get_dynamic_array_value_last_timestep(mut i: i32) -> f64 {
-    unsafe { ext_c::TRNSYSFUNCTIONS_mp_GETDYNAMICARRAYVALUELASTTIMESTEP(&mut i) }
+pub fn get_dynamic_array_value_last_timestep(i: StorageIndex) -> Result<f64, TrnSysError> {
+    const NAME: &str = "get_dynamic_array_value_last_timestep";
+    check_phase(NAME, RUNNING_PHASES)?;
+    let mut i = i.to_fortran(declared_counts(NAME)?.dynamic_storage)?;
+    Ok(unsafe { ext_c::TRNSYSFUNCTIONS_mp_GETDYNAMICARRAYVALUELASTTIMESTEP(&mut i) })
 }
 
-pub fn get_format(label: &mut [u8], iunit: &mut i32, no: &mut i32) -> String {
-    if label.len() > 256 {
-        panic!("The label length must be less than 256 bytes");
-    }
-    let mut buffer = vec![0 as c_char; get_max_path_length() as usize];
-    // write the label to the buffer
-    for (i, &byte) in label.iter().enumerate() {
-        buffer[i] = byte as c_char;
-    }
+/// Returns the format statement of the `no`-th label of the unit `iunit`.
+pub fn get_format(mut iunit: i32, mut no: i32) -> String {
+    let mut buffer = FortranBuffer::new(get_max_label_length());
     unsafe {
-        let ptr = ext_c::TRNSYSFUNCTIONS_mp_GETFORMAT(
-            buffer.as_mut_slice().as_mut_ptr(),
-            buffer.len(),
-            iunit,
-            no,
-        );
-        CStr::from_ptr(ptr).to_string_lossy().into_owned()
-    }
+        ext_c::TRNSYSFUNCTIONS_mp_GETFORMAT(buffer.as_mut_ptr(), buffer.len(), &mut iunit, &mut no)
+    };
+    buffer.to_string_lossy()
 }
 
-pub fn get_input_value(mut i: i32) -> f64 {
-    unsafe { ext_c::TRNSYSFUNCTIONS_mp_GETINPUTVALUE(&mut i) }
+pub fn get_input_value(i: InputIndex) -> Result<f64, TrnSysError> {
+    const NAME: &str = "get_input_value";
+    check_phase(NAME, RUNNING_PHASES)?;
+    let mut i = i.to_fortran(declared_counts(NAME)?.inputs)?;
+    Ok(unsafe { ext_c::TRNSYSFUNCTIONS_mp_GETINPUTVALUE(&mut i) })
 }
 
 ///Note that the function will return `true`, **no matter if a converged solution was found or not**
@@ -326,8 +422,11 @@ pub fn is_first_call_of_simulation() -> bool {
     unsafe { c_bool(ext_c::TRNSYSFUNCTIONS_mp_GETISFIRSTCALLOFSIMULATION()) }
 }
 
+/// Always `false` in kernels without the simulation summary report, see
+/// [`kernel_version::KernelFeature`].
 pub fn is_included_in_ssr() -> bool {
-    unsafe { c_bool(ext_c::TRNSYSFUNCTIONS_mp_GETISINCLUDEDINSSR()) }
+    kernel_function!(TRNSYSFUNCTIONS_mp_GETISINCLUDEDINSSR)
+        .is_some_and(|function| unsafe { c_bool(function()) })
 }
 
 pub fn is_last_call_of_simulation() -> bool {
@@ -347,30 +446,22 @@ pub fn is_version_signing_time() -> bool {
 }
 
 pub fn get_label(mut iunit: i32, mut no: i32) -> String {
-    let mut buffer = vec![0 as c_char; get_max_label_length() as usize];
+    let mut buffer = FortranBuffer::new(get_max_label_length());
     unsafe {
-        let ptr = ext_c::TRNSYSFUNCTIONS_mp_GETLABEL(
-            buffer.as_mut_slice().as_mut_ptr(),
-            buffer.capacity(),
+        ext_c::TRNSYSFUNCTIONS_mp_GETLABEL(
+            buffer.as_mut_ptr(),
+            buffer.len(),
             &mut iunit as *mut c_int,
             &mut no as *mut c_int,
-        );
-        CStr::from_ptr(buffer.as_ptr())
-            .to_string_lossy()
-            .into_owned()
-    }
+        )
+    };
+    buffer.to_string_lossy()
 }
 
 pub fn get_lu_filename(mut lu: i32) -> String {
-    let mut buffer = vec![0 as c_char; get_max_path_length() as usize];
-    unsafe {
-        let ptr = ext_c::TRNSYSFUNCTIONS_mp_GETLUFILENAME(
-            buffer.as_mut_slice().as_mut_ptr(),
-            buffer.len(),
-            &mut lu,
-        );
-        CStr::from_ptr(ptr).to_string_lossy().into_owned()
-    }
+    let mut buffer = FortranBuffer::new(get_max_path_length());
+    unsafe { ext_c::TRNSYSFUNCTIONS_mp_GETLUFILENAME(buffer.as_mut_ptr(), buffer.len(), &mut lu) };
+    buffer.to_string_lossy()
 }
 
 pub fn get_max_descrip_length() -> i32 {
@@ -417,12 +508,19 @@ pub fn get_numerical_solution(mut i: i32) -> f64 {
     unsafe { ext_c::TRNSYSFUNCTIONS_mp_GETNUMERICALSOLUTION(&mut i) }
 }
 
-pub fn get_output_value(mut i: i32) -> f64 {
-    unsafe { ext_c::TRNSYSFUNCTIONS_mp_GETOUTPUTVALUE(&mut i) }
+pub fn get_output_value(i: OutputIndex) -> Result<f64, TrnSysError> {
+    const NAME: &str = "get_output_value";
+    check_phase(NAME, RUNNING_PHASES)?;
+    let mut i = i.to_fortran(declared_counts(NAME)?.outputs)?;
+    Ok(unsafe { ext_c::TRNSYSFUNCTIONS_mp_GETOUTPUTVALUE(&mut i) })
 }
 
-pub fn get_parameter_value(mut i: i32) -> f64 {
-    unsafe { ext_c::TRNSYSFUNCTIONS_mp_GETPARAMETERVALUE(&mut i) }
+/// Parameters can already be read at the first call, e.g. to size the Type.
+pub fn get_parameter_value(i: ParamIndex) -> Result<f64, TrnSysError> {
+    const NAME: &str = "get_parameter_value";
+    check_phase(NAME, PARAMETER_PHASES)?;
+    let mut i = i.to_fortran(declared_counts(NAME)?.params)?;
+    Ok(unsafe { ext_c::TRNSYSFUNCTIONS_mp_GETPARAMETERVALUE(&mut i) })
 }
 
 pub fn get_previous_control_state(mut i: i32) -> i32 {
@@ -445,8 +543,11 @@ pub fn get_simulation_time_step() -> f64 {
     unsafe { ext_c::TRNSYSFUNCTIONS_mp_GETSIMULATIONTIMESTEP() }
 }
 
-pub fn get_static_array_value(mut i: i32) -> f64 {
-    unsafe { ext_c::TRNSYSFUNCTIONS_mp_GETSTATICARRAYVALUE(&mut i) }
+pub fn get_static_array_value(i: StorageIndex) -> Result<f64, TrnSysError> {
+    const NAME: &str = "get_static_array_value";
+    check_phase(NAME, RUNNING_PHASES)?;
+    let mut i = i.to_fortran(declared_counts(NAME)?.static_storage)?;
+    Ok(unsafe { ext_c::TRNSYSFUNCTIONS_mp_GETSTATICARRAYVALUE(&mut i) })
 }
 
 pub fn get_timestep_iteration() -> i32 {
@@ -454,38 +555,43 @@ pub fn get_timestep_iteration() -> i32 {
 }
 
 pub fn get_trnsys_input_file_dir() -> String {
-    let mut buffer = vec![0 as c_char; get_max_path_length() as usize];
-    unsafe {
-        let ptr = ext_c::TRNSYSFUNCTIONS_mp_GETTRNSYSINPUTFILEDIR(
-            buffer.as_mut_slice().as_mut_ptr(),
-            buffer.len(),
-        );
-        CStr::from_ptr(ptr).to_string_lossy().into_owned()
-    }
+    let mut buffer = FortranBuffer::new(get_max_path_length());
+    unsafe { ext_c::TRNSYSFUNCTIONS_mp_GETTRNSYSINPUTFILEDIR(buffer.as_mut_ptr(), buffer.len()) };
+    buffer.to_string_lossy()
 }
 
 pub fn get_trnsys_root_dir() -> String {
-    let mut buffer = vec![0 as c_char; get_max_path_length() as usize];
-    unsafe {
-        let ptr = ext_c::TRNSYSFUNCTIONS_mp_GETTRNSYSROOTDIR(
-            buffer.as_mut_slice().as_mut_ptr(),
-            buffer.len(),
-        );
-        CStr::from_ptr(ptr).to_string_lossy().into_owned()
-    }
+    let mut buffer = FortranBuffer::new(get_max_path_length());
+    unsafe { ext_c::TRNSYSFUNCTIONS_mp_GETTRNSYSROOTDIR(buffer.as_mut_ptr(), buffer.len()) };
+    buffer.to_string_lossy()
 }
 
 pub fn update_report_integral(index: &mut i32, int_val: &mut f64) {
-    unsafe { ext_c::UPDATEREPORTINTEGRAL(index, int_val) }
+    if let Some(function) = kernel_function!(UPDATEREPORTINTEGRAL) {
+        unsafe { function(index, int_val) }
+    }
 }
 
 pub fn update_report_min_max(index: &mut i32, new_val: &mut f64) {
-    unsafe { ext_c::UPDATEREPORTMINMAX(index, new_val) }
+    if let Some(function) = kernel_function!(UPDATEREPORTMINMAX) {
+        unsafe { function(index, new_val) }
+    }
 }
 
-pub fn log_message(severity: Severity, error_code: i32, message: &str) {
-    let severity = severity.as_cstring();
-    let message = std::ffi::CString::new(message).expect("CString::new failed");
+/// Writes a message of a unit to the list file. Messages longer than [`max_message_length`] are
+/// truncated.
+///
+/// The unit and type are passed by the caller, e.g. from the call span of a log event, as the
+/// kernel only knows the unit it is calling on its own thread.
+pub fn log_message(
+    severity: Severity,
+    error_code: i32,
+    unit: i32,
+    type_number: i32,
+    message: &str,
+) {
+    let mut severity = severity.as_fortran();
+    let mut message = FortranString::with_max_len(message, max_message_length());
 
     // 确保局部变量是mut的，然后通过指针传递
     let mut error_code = if (error_code < 1000 && error_code > 0) {
@@ -494,21 +600,18 @@ pub fn log_message(severity: Severity, error_code: i32, message: &str) {
         error_code
     };
 
-    let mut unit_no = get_current_unit();
-    let mut type_no = get_current_type();
-
-    let msg_len = message.as_bytes().len();
-    let sev_len = severity.as_bytes().len();
+    let mut unit_no = unit;
+    let mut type_no = type_number;
 
     unsafe {
         ext_c::MESSAGES(
             &mut error_code as *mut c_int,
-            message.as_ptr() as *mut c_char,
-            severity.as_ptr() as *mut c_char,
+            message.as_mut_ptr(),
+            severity.as_mut_ptr(),
             &mut unit_no as *mut c_int,
             &mut type_no as *mut c_int,
-            msg_len,
-            sev_len,
+            message.len(),
+            severity.len(),
         );
     }
 }
@@ -516,3 +619,24 @@ pub fn log_message(severity: Severity, error_code: i32, message: &str) {
 pub fn simulation_has_error() -> bool {
     unsafe { c_bool(ext_c::TRNSYSFUNCTIONS_mp_ERRORFOUND()) }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(mock_kernel)]
+    fn log_message_truncates_at_a_char_boundary() {
+        use crate::trnsys::mock_kernel::with_mock_kernel;
+
+        // the last "é" straddles the maximum length
+        let message = format!("{}é", "a".repeat(max_message_length() - 1));
+        log_message(Severity::Warning, 12, 3, 201, &message);
+
+        let messages = with_mock_kernel(|kernel| kernel.messages.clone());
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].message, "a".repeat(max_message_length() - 1));
+        assert_eq!(messages[0].code, 1012);
+        assert_eq!((messages[0].unit, messages[0].type_number), (3, 201));
+    }
+}
@@ -0,0 +1,164 @@
+use crate::trnsys::param::TrnSysValue;
+use tracing::info;
+
+/// How the inputs and parameters of a call are compared with the ones of the cached call.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputMatch {
+    /// The values must be bitwise equal.
+    Exact,
+    /// The values match if `|a - b| <= absolute + relative * max(|a|, |b|)`.
+    Tolerance { relative: f64, absolute: f64 },
+}
+
+impl InputMatch {
+    fn matches(&self, a: &[TrnSysValue], b: &[TrnSysValue]) -> bool {
+        a.len() == b.len()
+            && a.iter().zip(b).all(|(a, b)| match self {
+                InputMatch::Exact => a.value.to_bits() == b.value.to_bits(),
+                InputMatch::Tolerance { relative, absolute } => {
+                    (a.value - b.value).abs()
+                        <= absolute + relative * a.value.abs().max(b.value.abs())
+                }
+            })
+    }
+}
+
+/// Remembers the outputs of the last `iterate` call, so that they can be emitted again
+/// without recomputation if the kernel calls the Type with the same inputs and parameters.
+///
+/// The cache is disabled by default. It is only used for Types in `IterationMode::StaticMode`,
+/// whose outputs depend on their inputs alone.
+#[derive(Debug, Clone, Default)]
+pub struct OutputCache {
+    matching: Option<InputMatch>,
+    inputs: Vec<TrnSysValue>,
+    params: Vec<TrnSysValue>,
+    outputs: Option<Vec<TrnSysValue>>,
+    hits: u64,
+    misses: u64,
+}
+
+impl OutputCache {
+    /// Enables the cache with the given rule to compare inputs and parameters.
+    pub fn enable(&mut self, matching: InputMatch) {
+        self.matching = Some(matching);
+    }
+
+    pub fn disable(&mut self) {
+        *self = OutputCache::default();
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.matching.is_some()
+    }
+
+    /// Returns the cached outputs if the inputs and parameters match the cached call.
+    pub fn lookup(
+        &mut self,
+        inputs: &[TrnSysValue],
+        params: &[TrnSysValue],
+    ) -> Option<&[TrnSysValue]> {
+        let matching = self.matching?;
+        if self.outputs.is_some()
+            && matching.matches(&self.inputs, inputs)
+            && matching.matches(&self.params, params)
+        {
+            self.hits += 1;
+            self.outputs.as_deref()
+        } else {
+            self.misses += 1;
+            None
+        }
+    }
+
+    /// Stores the outputs computed for the given inputs and parameters.
+    pub fn store(
+        &mut self,
+        inputs: &[TrnSysValue],
+        params: &[TrnSysValue],
+        outputs: &[TrnSysValue],
+    ) {
+        if self.matching.is_none() {
+            return;
+        }
+        self.inputs.clear();
+        self.inputs.extend_from_slice(inputs);
+        self.params.clear();
+        self.params.extend_from_slice(params);
+        let cached = self.outputs.get_or_insert_with(Vec::new);
+        cached.clear();
+        cached.extend_from_slice(outputs);
+    }
+
+    /// Logs the hit and miss counters, called at the end of the simulation.
+    pub fn log_summary(&self, unit: i32) {
+        if self.is_enabled() {
+            info!(
+                "Unit {} output cache: {} hits, {} misses",
+                unit, self.hits, self.misses
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(values: &[f64]) -> Vec<TrnSysValue> {
+        values.iter().copied().map(TrnSysValue::new).collect()
+    }
+
+    fn cache(matching: InputMatch) -> OutputCache {
+        let mut cache = OutputCache::default();
+        cache.enable(matching);
+        cache.store(&values(&[1., f64::NAN]), &values(&[3.]), &values(&[42.]));
+        cache
+    }
+
+    #[test]
+    fn disabled_cache_misses() {
+        let mut cache = OutputCache::default();
+        cache.store(&values(&[1.]), &[], &values(&[42.]));
+        assert!(cache.lookup(&values(&[1.]), &[]).is_none());
+        assert_eq!((cache.hits, cache.misses), (0, 0));
+    }
+
+    #[test]
+    fn exact_match_hits_bitwise_equal_values() {
+        let mut cache = cache(InputMatch::Exact);
+        let hit = cache.lookup(&values(&[1., f64::NAN]), &values(&[3.]));
+        assert_eq!(hit, Some(&values(&[42.])[..]));
+        assert!(cache
+            .lookup(&values(&[1. + 1e-12, f64::NAN]), &values(&[3.]))
+            .is_none());
+        assert!(cache
+            .lookup(&values(&[1., f64::NAN]), &values(&[4.]))
+            .is_none());
+        assert!(cache.lookup(&values(&[1.]), &values(&[3.])).is_none());
+        assert_eq!((cache.hits, cache.misses), (1, 3));
+    }
+
+    #[test]
+    fn tolerance_match_hits_close_values() {
+        let mut cache = cache(InputMatch::Tolerance {
+            relative: 1e-3,
+            absolute: 0.,
+        });
+        cache.store(&values(&[1000.]), &[], &values(&[42.]));
+        assert!(cache.lookup(&values(&[1000.5]), &[]).is_some());
+        assert!(cache.lookup(&values(&[1002.]), &[]).is_none());
+    }
+
+    #[test]
+    fn store_replaces_the_cached_call() {
+        let mut cache = cache(InputMatch::Exact);
+        cache.store(&values(&[2.]), &[], &values(&[43.]));
+        assert!(cache
+            .lookup(&values(&[1., f64::NAN]), &values(&[3.]))
+            .is_none());
+        assert_eq!(cache.lookup(&values(&[2.]), &[]), Some(&values(&[43.])[..]));
+        cache.disable();
+        assert!(cache.lookup(&values(&[2.]), &[]).is_none());
+    }
+}
@@ -0,0 +1,202 @@
+use crate::trnsys::error::TrnSysError;
+use crate::trnsys::param::TrnSysValue;
+use crate::trnsys::{CallContext, OutputIndex};
+use std::ops::RangeInclusive;
+use tracing::warn;
+
+/// What the guard does with an output which is not finite or out of its declared range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputPolicy {
+    /// Log a warning and write the value anyway.
+    #[default]
+    Warn,
+    /// Clamp the value into its declared range. Values without a declared range and NaN
+    /// are replaced by the last good value instead.
+    Clamp,
+    /// Replace the value by the last good value of the output.
+    HoldLastGood,
+    /// Stop the simulation with a fatal error.
+    Fail,
+}
+
+/// Checks every output before it is written to the kernel, so that a NaN or an infinity
+/// is reported where it is produced, and not later in another component.
+#[derive(Debug, Clone, Default)]
+pub struct OutputGuard {
+    pub policy: OutputPolicy,
+    ranges: Vec<Option<RangeInclusive<f64>>>,
+    last_good: Vec<f64>,
+}
+
+impl OutputGuard {
+    /// Declares the physical range of an output. Values out of range are handled by the policy.
+    pub fn set_range(&mut self, i: OutputIndex, range: RangeInclusive<f64>) {
+        if self.ranges.len() <= i.0 {
+            self.ranges.resize(i.0 + 1, None);
+        }
+        self.ranges[i.0] = Some(range);
+    }
+
+    /// Sets the values used by [`OutputPolicy::HoldLastGood`] before the first good value.
+    pub fn reset(&mut self, initial_values: &[TrnSysValue]) {
+        self.last_good = initial_values.iter().map(|value| value.value).collect();
+    }
+
+    /// Checks the outputs computed for the given inputs and parameters and applies the policy
+    /// to the bad ones.
+    pub fn check(
+        &mut self,
+        ctx: &CallContext,
+        inputs: &[TrnSysValue],
+        params: &[TrnSysValue],
+        outputs: &mut [TrnSysValue],
+    ) -> Result<(), TrnSysError> {
+        if self.last_good.len() < outputs.len() {
+            self.last_good.resize(outputs.len(), 0.);
+        }
+        for (i, output) in outputs.iter_mut().enumerate() {
+            let range = self.ranges.get(i).cloned().flatten();
+            let value = output.value;
+            let in_range = range.as_ref().is_none_or(|range| range.contains(&value));
+            if value.is_finite() && in_range {
+                self.last_good[i] = value;
+                continue;
+            }
+
+            let index = OutputIndex(i);
+            let reason = if value.is_finite() {
+                format!(
+                    "{} = {} is out of range {:?}",
+                    index,
+                    value,
+                    range.as_ref().unwrap()
+                )
+            } else {
+                format!("{} = {} is not finite", index, value)
+            };
+            let diagnostics = format!(
+                "{} at t={} (iteration {}), inputs: {:?}, parameters: {:?}",
+                reason,
+                ctx.time,
+                ctx.iteration,
+                values(inputs),
+                values(params)
+            );
+
+            output.value = match self.policy {
+                OutputPolicy::Warn => {
+                    warn!("{}", diagnostics);
+                    value
+                }
+                OutputPolicy::Clamp => {
+                    let replacement = match &range {
+                        Some(range) if !value.is_nan() => value.clamp(*range.start(), *range.end()),
+                        _ => self.last_good[i],
+                    };
+                    warn!("{}, replaced by {}", diagnostics, replacement);
+                    replacement
+                }
+                OutputPolicy::HoldLastGood => {
+                    let replacement = self.last_good[i];
+                    warn!("{}, replaced by {}", diagnostics, replacement);
+                    replacement
+                }
+                OutputPolicy::Fail => {
+                    return Err(TrnSysError::BadOutput {
+                        index,
+                        message: diagnostics,
+                    });
+                }
+            };
+        }
+        Ok(())
+    }
+}
+
+fn values(values: &[TrnSysValue]) -> Vec<f64> {
+    values.iter().map(|value| value.value).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trnsys::CallPhase;
+
+    fn check(guard: &mut OutputGuard, value: f64) -> Result<f64, TrnSysError> {
+        let ctx = CallContext::new(CallPhase::Iteration, 1., 1., 1, 1, 256);
+        let mut outputs = [TrnSysValue::new(value)];
+        guard.check(&ctx, &[], &[], &mut outputs)?;
+        Ok(outputs[0].value)
+    }
+
+    fn guard(policy: OutputPolicy) -> OutputGuard {
+        let mut guard = OutputGuard {
+            policy,
+            ..OutputGuard::default()
+        };
+        guard.set_range(OutputIndex(0), 0.0..=100.);
+        guard.reset(&[TrnSysValue::new(20.)]);
+        guard
+    }
+
+    #[test]
+    fn warn_keeps_the_value() {
+        let mut guard = guard(OutputPolicy::Warn);
+        assert_eq!(check(&mut guard, 150.).unwrap(), 150.);
+        assert!(check(&mut guard, f64::NAN).unwrap().is_nan());
+    }
+
+    #[test]
+    fn clamp_clamps_into_the_range() {
+        let mut guard = guard(OutputPolicy::Clamp);
+        assert_eq!(check(&mut guard, 150.).unwrap(), 100.);
+        assert_eq!(check(&mut guard, -5.).unwrap(), 0.);
+        assert_eq!(check(&mut guard, 40.).unwrap(), 40.);
+        // NaN has no place in the range, the last good value is held
+        assert_eq!(check(&mut guard, f64::NAN).unwrap(), 40.);
+        assert_eq!(check(&mut guard, f64::INFINITY).unwrap(), 100.);
+    }
+
+    #[test]
+    fn clamp_holds_outputs_without_a_range() {
+        let mut guard = OutputGuard {
+            policy: OutputPolicy::Clamp,
+            ..OutputGuard::default()
+        };
+        assert_eq!(check(&mut guard, 1e9).unwrap(), 1e9);
+        assert_eq!(check(&mut guard, f64::NEG_INFINITY).unwrap(), 1e9);
+    }
+
+    #[test]
+    fn hold_last_good_starts_from_the_initial_values() {
+        let mut guard = guard(OutputPolicy::HoldLastGood);
+        assert_eq!(check(&mut guard, f64::NAN).unwrap(), 20.);
+        assert_eq!(check(&mut guard, 30.).unwrap(), 30.);
+        assert_eq!(check(&mut guard, 150.).unwrap(), 30.);
+    }
+
+    #[test]
+    fn fail_reports_the_output() {
+        let mut guard = guard(OutputPolicy::Fail);
+        let ctx = CallContext::new(CallPhase::Iteration, 2.5, 1., 3, 1, 256);
+        let mut outputs = [TrnSysValue::new(150.)];
+        let inputs = [TrnSysValue::new(7.)];
+        let params = [TrnSysValue::new(2.)];
+        let error = guard
+            .check(&ctx, &inputs, &params, &mut outputs)
+            .unwrap_err();
+        let TrnSysError::BadOutput { index, message } = error else {
+            panic!("{:?}", error);
+        };
+        assert_eq!(index, OutputIndex(0));
+        assert_eq!(
+            message,
+            "output 1 = 150 is out of range 0.0..=100.0 at t=2.5 (iteration 3), \
+            inputs: [7.0], parameters: [2.0]"
+        );
+        assert!(matches!(
+            check(&mut guard, f64::NAN),
+            Err(TrnSysError::BadOutput { message, .. }) if message.contains("is not finite")
+        ));
+    }
+}
@@ -1,7 +1,8 @@
 use crate::trnsys::error::TrnSysError;
-use crate::trnsys::get_lu_filename;
+use crate::trnsys::{get_lu_filename, ParamIndex};
 use std::panic::catch_unwind;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct TrnSysValue {
     pub value: f64,
 }
@@ -77,3 +78,86 @@ impl TryFrom<&TrnSysValue> for String {
         }
     }
 }
+
+/// The parameters before and after the kernel asked to reread them, with the indices of the ones
+/// which changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParameterDiff {
+    pub old: Vec<TrnSysValue>,
+    pub new: Vec<TrnSysValue>,
+    /// The parameters whose value changed, in ascending order.
+    pub changed: Vec<ParamIndex>,
+}
+
+impl ParameterDiff {
+    /// Compares the values bitwise, so that a NaN parameter which stays NaN is unchanged.
+    pub fn new(old: &[TrnSysValue], new: &[TrnSysValue]) -> Self {
+        let changed = (0..old.len().max(new.len()))
+            .filter(|&i| {
+                old.get(i).map(|value| value.value.to_bits())
+                    != new.get(i).map(|value| value.value.to_bits())
+            })
+            .map(ParamIndex)
+            .collect();
+        ParameterDiff {
+            old: old.to_vec(),
+            new: new.to_vec(),
+            changed,
+        }
+    }
+
+    /// The diff of the parameters, or `None` if none changed. Only allocates if one did, so that
+    /// rereading unchanged parameters is free.
+    pub fn between(old: &[TrnSysValue], new: &[TrnSysValue]) -> Option<Self> {
+        let unchanged = old.len() == new.len()
+            && old
+                .iter()
+                .zip(new)
+                .all(|(old, new)| old.value.to_bits() == new.value.to_bits());
+        (!unchanged).then(|| ParameterDiff::new(old, new))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty()
+    }
+
+    pub fn is_changed(&self, i: ParamIndex) -> bool {
+        self.changed.binary_search(&i).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(values: &[f64]) -> Vec<TrnSysValue> {
+        values.iter().copied().map(TrnSysValue::new).collect()
+    }
+
+    #[test]
+    fn converts_integral_values_to_i32() {
+        assert_eq!(i32::try_from(&TrnSysValue::new(-3.)).unwrap(), -3);
+        assert!(i32::try_from(&TrnSysValue::new(2.5)).is_err());
+        assert!(i32::try_from(&TrnSysValue::new(f64::NAN)).is_err());
+    }
+
+    #[test]
+    fn lists_the_changed_parameters() {
+        let diff = ParameterDiff::new(&values(&[1., 2., 3.]), &values(&[1., 5., 3., 4.]));
+        assert_eq!(diff.changed, [ParamIndex(1), ParamIndex(3)]);
+        assert!(diff.is_changed(ParamIndex(1)));
+        assert!(!diff.is_changed(ParamIndex(2)));
+        assert_eq!(diff.new[1].value, 5.);
+    }
+
+    #[test]
+    fn compares_bitwise() {
+        let old = values(&[f64::NAN, 0.]);
+        assert!(ParameterDiff::new(&old, &old).is_empty());
+        assert!(ParameterDiff::between(&old, &old).is_none());
+        // -0 is another value for the kernel
+        let diff = ParameterDiff::between(&old, &values(&[f64::NAN, -0.])).unwrap();
+        assert_eq!(diff.changed, [ParamIndex(1)]);
+        assert!(ParameterDiff::between(&old, &old[..1]).is_some());
+    }
+}
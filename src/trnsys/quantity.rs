@@ -0,0 +1,128 @@
+use crate::trnsys::error::TrnSysError;
+use crate::trnsys::param::TrnSysValue;
+use crate::trnsys::unit_catalog::{Dimension, TrnSysUnit};
+
+/// A physical quantity stored in SI units.
+pub trait Quantity: Copy {
+    const DIMENSION: Dimension;
+
+    fn from_si(si: f64) -> Self;
+
+    fn si(&self) -> f64;
+}
+
+macro_rules! quantity {
+    ($(#[$meta:meta])* $name:ident, $dimension:expr) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+        pub struct $name(pub f64);
+
+        impl Quantity for $name {
+            const DIMENSION: Dimension = $dimension;
+
+            fn from_si(si: f64) -> Self {
+                $name(si)
+            }
+
+            fn si(&self) -> f64 {
+                self.0
+            }
+        }
+    };
+}
+
+quantity!(
+    /// A dimensionless value.
+    Ratio,
+    Dimension::Dimensionless
+);
+quantity!(
+    /// A temperature in K.
+    Temperature,
+    Dimension::Temperature
+);
+quantity!(
+    /// A power or heat flow in W.
+    Power,
+    Dimension::Power
+);
+quantity!(
+    /// A mass flow rate in kg/s.
+    MassFlowRate,
+    Dimension::MassFlowRate
+);
+quantity!(
+    /// An energy in J.
+    Energy,
+    Dimension::Energy
+);
+
+impl Temperature {
+    pub fn from_celsius(celsius: f64) -> Self {
+        Temperature(TrnSysUnit::Celsius.convert_to_si(celsius))
+    }
+
+    pub fn celsius(&self) -> f64 {
+        TrnSysUnit::Celsius.convert_from_si(self.0)
+    }
+}
+
+impl TrnSysValue {
+    /// Interprets the value as given in `unit` and converts it to the quantity `Q`.
+    pub fn to_quantity<Q: Quantity>(self, unit: TrnSysUnit) -> Result<Q, TrnSysError> {
+        check_dimension::<Q>(unit)?;
+        Ok(Q::from_si(unit.convert_to_si(self.value)))
+    }
+
+    /// Converts the quantity to a value in `unit`.
+    pub fn from_quantity<Q: Quantity>(quantity: Q, unit: TrnSysUnit) -> Result<Self, TrnSysError> {
+        check_dimension::<Q>(unit)?;
+        Ok(TrnSysValue::new(unit.convert_from_si(quantity.si())))
+    }
+}
+
+fn check_dimension<Q: Quantity>(unit: TrnSysUnit) -> Result<(), TrnSysError> {
+    if unit.dimension() == Q::DIMENSION {
+        Ok(())
+    } else {
+        Err(TrnSysError::DimensionMismatch {
+            expected: Q::DIMENSION,
+            unit,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temperatures_are_stored_in_kelvin() {
+        let temperature = Temperature::from_celsius(20.);
+        assert!((temperature.si() - 293.15).abs() < 1e-9);
+        assert!((temperature.celsius() - 20.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn converts_values_to_and_from_quantities() {
+        let power: Power = TrnSysValue::new(3600.)
+            .to_quantity(TrnSysUnit::KiloJoulePerHour)
+            .unwrap();
+        assert!((power.0 - 1000.).abs() < 1e-9);
+        let value = TrnSysValue::from_quantity(power, TrnSysUnit::KiloWatt).unwrap();
+        assert!((value.value - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rejects_units_of_another_dimension() {
+        let result = TrnSysValue::new(1.).to_quantity::<MassFlowRate>(TrnSysUnit::Watt);
+        assert!(matches!(
+            result,
+            Err(TrnSysError::DimensionMismatch {
+                expected: Dimension::MassFlowRate,
+                unit: TrnSysUnit::Watt
+            })
+        ));
+        assert!(TrnSysValue::from_quantity(Ratio(0.5), TrnSysUnit::Celsius).is_err());
+    }
+}
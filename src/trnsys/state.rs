@@ -1,72 +1,170 @@
+use crate::trnsys::convergence::ConvergenceTracker;
+use crate::trnsys::dimensions::Sizes;
+use crate::trnsys::error::TrnSysError;
+use crate::trnsys::index::DeclaredCounts;
+use crate::trnsys::input_check::InputChecker;
 use crate::trnsys::iteration_mode::IterationMode;
+use crate::trnsys::kernel_version::TrnSysVersion;
+use crate::trnsys::output_cache::OutputCache;
+use crate::trnsys::output_guard::OutputGuard;
 use crate::trnsys::param::TrnSysValue;
+use crate::trnsys::quantity::Quantity;
+use crate::trnsys::unit_catalog::TrnSysUnit;
 use crate::trnsys::{
-    get_current_unit, get_input_value, get_label, get_output_value, get_parameter_value,
+    get_current_unit, get_input_value, get_label, get_number_of_labels, get_output_value,
+    get_parameter_value, InputIndex, OutputIndex, ParamIndex,
 };
 
 pub(crate) struct TrnSysState {
-    pub(crate) trnsys_standard_version: i32,
+    /// The version the Type signed with, which is not newer than the kernel.
+    pub(crate) type_version: TrnSysVersion,
     pub(crate) num_params: i32,
     pub(crate) params: Vec<TrnSysValue>,
-    pub(crate) num_labels: i32,
-    pub(crate) labels: Vec<String>,
+    /// The parameters before they are reread, compared in place with the new values.
+    pub(crate) previous_params: Vec<TrnSysValue>,
+    /// Fetched from the kernel at the first access, see [`TrnSysState::labels`].
+    labels: Option<Vec<String>>,
     pub(crate) num_inputs: i32,
     pub(crate) inputs: Vec<TrnSysValue>,
+    /// The units of the inputs, declared to the kernel at the first call.
+    pub(crate) input_units: Vec<TrnSysUnit>,
+    pub(crate) input_check: InputChecker,
     pub(crate) num_derivatives: i32,
     pub(crate) num_outputs: i32,
     pub(crate) default_output_values: Vec<TrnSysValue>,
+    /// The outputs computed at the last iteration, written to the kernel after `iterate`.
     pub(crate) outputs: Vec<TrnSysValue>,
+    /// The units of the outputs, declared to the kernel at the first call.
+    pub(crate) output_units: Vec<TrnSysUnit>,
+    pub(crate) num_static_storage: i32,
+    pub(crate) num_dynamic_storage: i32,
+    /// The counts and cycles computed in the sizing phase at the first call.
+    pub(crate) sizes: Sizes,
     pub(crate) iteration_mode: IterationMode,
+    pub(crate) convergence: ConvergenceTracker,
+    pub(crate) output_cache: OutputCache,
+    pub(crate) output_guard: OutputGuard,
 }
 
 impl TrnSysState {
     /// set up parameters for the TRNSYS type
     pub fn new() -> Self {
         TrnSysState {
-            trnsys_standard_version: 18,
+            type_version: TrnSysVersion::of_type(),
             iteration_mode: IterationMode::default(),
 
             num_params: 0,
             params: vec![],
+            previous_params: vec![],
 
-            num_labels: 0,
-            labels: vec![],
+            labels: None,
 
             num_inputs: 0,
             inputs: vec![],
+            input_units: vec![],
+            input_check: InputChecker::default(),
 
             num_derivatives: 0,
 
             num_outputs: 0,
             outputs: vec![],
+            output_units: vec![],
             default_output_values: vec![],
 
+            num_static_storage: 0,
+            num_dynamic_storage: 0,
+            sizes: Sizes::default(),
+
+            convergence: ConvergenceTracker::default(),
+            output_cache: OutputCache::default(),
+            output_guard: OutputGuard::default(),
+        }
+    }
+
+    /// The counts the kernel wrappers check their indices against.
+    pub fn declared_counts(&self) -> DeclaredCounts {
+        DeclaredCounts {
+            inputs: self.num_inputs,
+            outputs: self.num_outputs,
+            params: self.num_params,
+            static_storage: self.num_static_storage,
+            dynamic_storage: self.num_dynamic_storage,
         }
     }
 
-    pub fn read_parameter_values(&mut self) {
-        self.params = (1..self.num_params + 1)
-            .map(|i| TrnSysValue {
-                value: get_parameter_value(i),
-            })
-            .collect();
+    /// Reads an input as a quantity, converted from the declared unit of the input.
+    pub fn input<Q: Quantity>(&self, i: InputIndex) -> Result<Q, TrnSysError> {
+        let unit = self
+            .input_units
+            .get(i.0)
+            .ok_or(TrnSysError::UndeclaredUnit {
+                kind: InputIndex::KIND,
+                index: i.0,
+            })?;
+        let value = self.inputs.get(i.0).ok_or(TrnSysError::IndexOutOfRange {
+            kind: InputIndex::KIND,
+            index: i.0,
+            count: self.inputs.len() as i32,
+        })?;
+        value.to_quantity(*unit)
+    }
+
+    /// Converts a quantity to the declared unit of an output.
+    pub fn output<Q: Quantity>(
+        &self,
+        i: OutputIndex,
+        quantity: Q,
+    ) -> Result<TrnSysValue, TrnSysError> {
+        let unit = self
+            .output_units
+            .get(i.0)
+            .ok_or(TrnSysError::UndeclaredUnit {
+                kind: OutputIndex::KIND,
+                index: i.0,
+            })?;
+        TrnSysValue::from_quantity(quantity, *unit)
+    }
 
-        self.labels = (1..self.num_labels + 1)
-            .map(|i| get_label(get_current_unit(), i))
-            .collect();
+    /// Sizes the buffers of the parameters, inputs and outputs to the declared counts, once the
+    /// counts are known at the first call. The calls after it read and write in place.
+    /// The parameters read by the sizing phase are kept.
+    pub fn allocate_buffers(&mut self) {
+        let zero = TrnSysValue::new(0.);
+        self.params.resize(self.num_params.max(0) as usize, zero);
+        self.previous_params = self.params.clone();
+        self.inputs = vec![zero; self.num_inputs.max(0) as usize];
+        self.outputs = vec![zero; self.num_outputs.max(0) as usize];
     }
 
-    pub fn read_input_values(&mut self) {
-        self.inputs = (1..self.num_inputs + 1)
-            .map(|i| TrnSysValue {
-                value: get_input_value(i),
-            })
-            .collect();
-
-        self.outputs = (1..self.num_outputs + 1)
-            .map(|i| TrnSysValue {
-                value: get_output_value(i),
-            })
-            .collect();
+    /// The labels of the unit. They are fetched from the kernel at the first access after the
+    /// parameters were read, since most Types never use them.
+    pub fn labels(&mut self) -> &[String] {
+        self.labels.get_or_insert_with(|| {
+            let unit = get_current_unit();
+            (1..=get_number_of_labels(unit))
+                .map(|i| get_label(unit, i))
+                .collect()
+        })
+    }
+
+    /// Reads the value the kernel currently holds for an output. The outputs are not read at
+    /// every call, only when asked for.
+    pub fn kernel_output(&self, i: OutputIndex) -> Result<TrnSysValue, TrnSysError> {
+        get_output_value(i).map(TrnSysValue::from)
+    }
+
+    pub fn read_parameter_values(&mut self) -> Result<(), TrnSysError> {
+        for (i, param) in self.params.iter_mut().enumerate() {
+            param.value = get_parameter_value(ParamIndex(i))?;
+        }
+        self.labels = None;
+        Ok(())
+    }
+
+    pub fn read_input_values(&mut self) -> Result<(), TrnSysError> {
+        for (i, input) in self.inputs.iter_mut().enumerate() {
+            input.value = get_input_value(InputIndex(i))?;
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,368 @@
+use crate::trnsys::error::TrnSysError;
+use crate::trnsys::InputIndex;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The physical dimension of a value exchanged with the kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dimension {
+    Dimensionless,
+    Temperature,
+    TemperatureDifference,
+    Power,
+    MassFlowRate,
+    Energy,
+    /// Power per area, e.g. solar irradiance.
+    HeatFlux,
+    HeatTransferCoefficient,
+    SpecificHeat,
+    Density,
+    Length,
+    Area,
+    Volume,
+    Time,
+    Angle,
+}
+
+/// A standard TRNSYS unit, identified by its unit code, e.g. "TE1" for °C.
+///
+/// The unit codes are used by the kernel and Simulation Studio to check that connected
+/// outputs and inputs have the same dimension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrnSysUnit {
+    /// DM1: -
+    Dimensionless,
+    /// TE1: °C
+    Celsius,
+    /// TE2: °F
+    Fahrenheit,
+    /// TE3: K
+    Kelvin,
+    /// DT1: ΔK
+    DeltaKelvin,
+    /// PW1: kJ/h, the TRNSYS default for power.
+    KiloJoulePerHour,
+    /// PW2: W
+    Watt,
+    /// PW3: kW
+    KiloWatt,
+    /// MF1: kg/h, the TRNSYS default for mass flow rates.
+    KilogramPerHour,
+    /// MF2: kg/s
+    KilogramPerSecond,
+    /// EN1: kJ
+    KiloJoule,
+    /// IR1: kJ/h·m²
+    KiloJoulePerHourSquareMeter,
+    /// IR2: W/m²
+    WattPerSquareMeter,
+    /// HT1: kJ/h·m²·K
+    KiloJoulePerHourSquareMeterKelvin,
+    /// CP1: kJ/kg·K
+    KiloJoulePerKilogramKelvin,
+    /// DN1: kg/m³
+    KilogramPerCubicMeter,
+    /// LE1: m
+    Meter,
+    /// AR1: m²
+    SquareMeter,
+    /// VL1: m³
+    CubicMeter,
+    /// TM1: h
+    Hour,
+    /// DG1: °
+    Degree,
+}
+
+/// Code, symbol, dimension and conversion to SI of a unit: `si = value * factor + offset`.
+struct UnitInfo {
+    code: &'static str,
+    symbol: &'static str,
+    dimension: Dimension,
+    factor: f64,
+    offset: f64,
+}
+
+const fn info(
+    code: &'static str,
+    symbol: &'static str,
+    dimension: Dimension,
+    factor: f64,
+    offset: f64,
+) -> UnitInfo {
+    UnitInfo {
+        code,
+        symbol,
+        dimension,
+        factor,
+        offset,
+    }
+}
+
+impl TrnSysUnit {
+    /// All the units of the catalog.
+    pub const ALL: &'static [TrnSysUnit] = &[
+        TrnSysUnit::Dimensionless,
+        TrnSysUnit::Celsius,
+        TrnSysUnit::Fahrenheit,
+        TrnSysUnit::Kelvin,
+        TrnSysUnit::DeltaKelvin,
+        TrnSysUnit::KiloJoulePerHour,
+        TrnSysUnit::Watt,
+        TrnSysUnit::KiloWatt,
+        TrnSysUnit::KilogramPerHour,
+        TrnSysUnit::KilogramPerSecond,
+        TrnSysUnit::KiloJoule,
+        TrnSysUnit::KiloJoulePerHourSquareMeter,
+        TrnSysUnit::WattPerSquareMeter,
+        TrnSysUnit::KiloJoulePerHourSquareMeterKelvin,
+        TrnSysUnit::KiloJoulePerKilogramKelvin,
+        TrnSysUnit::KilogramPerCubicMeter,
+        TrnSysUnit::Meter,
+        TrnSysUnit::SquareMeter,
+        TrnSysUnit::CubicMeter,
+        TrnSysUnit::Hour,
+        TrnSysUnit::Degree,
+    ];
+
+    fn info(self) -> UnitInfo {
+        use Dimension::*;
+        match self {
+            TrnSysUnit::Dimensionless => info("DM1", "-", Dimensionless, 1., 0.),
+            TrnSysUnit::Celsius => info("TE1", "C", Temperature, 1., 273.15),
+            TrnSysUnit::Fahrenheit => {
+                info("TE2", "F", Temperature, 5. / 9., 273.15 - 32. * 5. / 9.)
+            }
+            TrnSysUnit::Kelvin => info("TE3", "K", Temperature, 1., 0.),
+            TrnSysUnit::DeltaKelvin => info("DT1", "deltaK", TemperatureDifference, 1., 0.),
+            TrnSysUnit::KiloJoulePerHour => info("PW1", "kJ/hr", Power, 1000. / 3600., 0.),
+            TrnSysUnit::Watt => info("PW2", "W", Power, 1., 0.),
+            TrnSysUnit::KiloWatt => info("PW3", "kW", Power, 1000., 0.),
+            TrnSysUnit::KilogramPerHour => info("MF1", "kg/hr", MassFlowRate, 1. / 3600., 0.),
+            TrnSysUnit::KilogramPerSecond => info("MF2", "kg/s", MassFlowRate, 1., 0.),
+            TrnSysUnit::KiloJoule => info("EN1", "kJ", Energy, 1000., 0.),
+            TrnSysUnit::KiloJoulePerHourSquareMeter => {
+                info("IR1", "kJ/hr.m^2", HeatFlux, 1000. / 3600., 0.)
+            }
+            TrnSysUnit::WattPerSquareMeter => info("IR2", "W/m^2", HeatFlux, 1., 0.),
+            TrnSysUnit::KiloJoulePerHourSquareMeterKelvin => info(
+                "HT1",
+                "kJ/hr.m^2.K",
+                HeatTransferCoefficient,
+                1000. / 3600.,
+                0.,
+            ),
+            TrnSysUnit::KiloJoulePerKilogramKelvin => {
+                info("CP1", "kJ/kg.K", SpecificHeat, 1000., 0.)
+            }
+            TrnSysUnit::KilogramPerCubicMeter => info("DN1", "kg/m^3", Density, 1., 0.),
+            TrnSysUnit::Meter => info("LE1", "m", Length, 1., 0.),
+            TrnSysUnit::SquareMeter => info("AR1", "m^2", Area, 1., 0.),
+            TrnSysUnit::CubicMeter => info("VL1", "m^3", Volume, 1., 0.),
+            TrnSysUnit::Hour => info("TM1", "hr", Time, 3600., 0.),
+            TrnSysUnit::Degree => info("DG1", "degrees", Angle, 1., 0.),
+        }
+    }
+
+    pub fn dimension(&self) -> Dimension {
+        self.info().dimension
+    }
+
+    /// The unit code passed to `set_input_units` and `set_output_units`.
+    pub fn code(&self) -> &'static str {
+        self.info().code
+    }
+
+    /// The symbol of the unit as displayed by TRNSYS.
+    pub fn symbol(&self) -> &'static str {
+        self.info().symbol
+    }
+
+    /// Looks up a unit by its TRNSYS unit code, e.g. "TE1".
+    pub fn from_code(code: &str) -> Option<Self> {
+        let code = code.trim();
+        TrnSysUnit::ALL
+            .iter()
+            .copied()
+            .find(|unit| unit.code().eq_ignore_ascii_case(code))
+    }
+
+    pub fn convert_to_si(self, value: f64) -> f64 {
+        let info = self.info();
+        value * info.factor + info.offset
+    }
+
+    pub fn convert_from_si(self, si: f64) -> f64 {
+        let info = self.info();
+        (si - info.offset) / info.factor
+    }
+
+    /// Converts a value of this unit to `target`, which must have the same dimension.
+    pub fn convert_to(self, value: f64, target: TrnSysUnit) -> Result<f64, TrnSysError> {
+        check_connection(self, target)?;
+        Ok(target.convert_from_si(self.convert_to_si(value)))
+    }
+}
+
+impl Display for TrnSysUnit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.symbol(), self.code())
+    }
+}
+
+impl FromStr for TrnSysUnit {
+    type Err = TrnSysError;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        TrnSysUnit::from_code(code).ok_or_else(|| TrnSysError::UnknownUnitCode(code.to_string()))
+    }
+}
+
+/// Checks that an output declared in `output` can be connected to an input declared in `input`.
+///
+/// The check doesn't need the kernel, so declared units can be checked offline, e.g. against
+/// the unit codes of the proforma of the connected Type:
+///
+/// ```ignore
+/// check_connection("TE3".parse()?, TrnSysUnit::Celsius)?;
+/// ```
+pub fn check_connection(output: TrnSysUnit, input: TrnSysUnit) -> Result<(), TrnSysError> {
+    if output.dimension() == input.dimension() {
+        Ok(())
+    } else {
+        Err(TrnSysError::IncompatibleUnits { output, input })
+    }
+}
+
+/// Checks the units the Type declares for its inputs against the units of the outputs connected to
+/// them in a deck, e.g. taken from the proformas of the upstream Types. Returns the connections
+/// which cannot be made, with the input they feed.
+///
+/// The template runs it in `cargo test` against the list of connected outputs in
+/// `src/trnsys_type.rs`, so the connections are checked before the deck is run.
+pub fn check_connections(
+    input_units: &[TrnSysUnit],
+    connected_outputs: &[(InputIndex, TrnSysUnit)],
+) -> Vec<(InputIndex, TrnSysError)> {
+    connected_outputs
+        .iter()
+        .filter_map(|&(input, output)| {
+            let result = match input_units.get(input.0) {
+                Some(&unit) => check_connection(output, unit),
+                None => Err(TrnSysError::UndeclaredUnit {
+                    kind: InputIndex::KIND,
+                    index: input.0,
+                }),
+            };
+            result.err().map(|e| (input, e))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() <= 1e-9 * expected.abs().max(1.),
+            "{actual} != {expected}"
+        );
+    }
+
+    #[test]
+    fn units_of_the_same_dimension_can_be_connected() {
+        assert!(check_connection(TrnSysUnit::Kelvin, TrnSysUnit::Celsius).is_ok());
+        assert!(check_connection(TrnSysUnit::Watt, TrnSysUnit::KiloJoulePerHour).is_ok());
+        assert!(
+            check_connection(TrnSysUnit::KilogramPerSecond, TrnSysUnit::KilogramPerHour).is_ok()
+        );
+    }
+
+    #[test]
+    fn units_of_other_dimensions_cannot_be_connected() {
+        assert!(matches!(
+            check_connection(TrnSysUnit::Watt, TrnSysUnit::KilogramPerHour),
+            Err(TrnSysError::IncompatibleUnits {
+                output: TrnSysUnit::Watt,
+                input: TrnSysUnit::KilogramPerHour
+            })
+        ));
+        // a temperature difference is not a temperature
+        assert!(check_connection(TrnSysUnit::DeltaKelvin, TrnSysUnit::Celsius).is_err());
+        assert!(TrnSysUnit::Celsius
+            .convert_to(20., TrnSysUnit::KiloJoule)
+            .is_err());
+    }
+
+    #[test]
+    fn converts_to_si() {
+        assert_close(TrnSysUnit::Celsius.convert_to_si(20.), 293.15);
+        assert_close(TrnSysUnit::Fahrenheit.convert_to_si(212.), 373.15);
+        assert_close(TrnSysUnit::KiloJoulePerHour.convert_to_si(3600.), 1000.);
+        assert_close(TrnSysUnit::KiloWatt.convert_to_si(1.), 1000.);
+        assert_close(TrnSysUnit::KilogramPerHour.convert_to_si(3600.), 1.);
+        assert_close(TrnSysUnit::KiloJoule.convert_to_si(1.), 1000.);
+        assert_close(
+            TrnSysUnit::KiloJoulePerHourSquareMeter.convert_to_si(3.6),
+            1.,
+        );
+        assert_close(
+            TrnSysUnit::KiloJoulePerKilogramKelvin.convert_to_si(4.19),
+            4190.,
+        );
+        assert_close(TrnSysUnit::Hour.convert_to_si(1.), 3600.);
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        for &unit in TrnSysUnit::ALL {
+            assert_close(unit.convert_from_si(unit.convert_to_si(42.)), 42.);
+        }
+        assert_close(
+            TrnSysUnit::Celsius
+                .convert_to(100., TrnSysUnit::Fahrenheit)
+                .unwrap(),
+            212.,
+        );
+        assert_close(
+            TrnSysUnit::Watt
+                .convert_to(1000., TrnSysUnit::KiloJoulePerHour)
+                .unwrap(),
+            3600.,
+        );
+    }
+
+    #[test]
+    fn looks_up_unit_codes() {
+        for &unit in TrnSysUnit::ALL {
+            assert_eq!(TrnSysUnit::from_code(unit.code()), Some(unit));
+        }
+        assert_eq!(" te1 ".parse::<TrnSysUnit>().unwrap(), TrnSysUnit::Celsius);
+        assert!(matches!(
+            "XX9".parse::<TrnSysUnit>(),
+            Err(TrnSysError::UnknownUnitCode(code)) if code == "XX9"
+        ));
+    }
+
+    #[test]
+    fn reports_the_inputs_of_incompatible_connections() {
+        let input_units = [TrnSysUnit::Celsius, TrnSysUnit::KilogramPerHour];
+        let errors = check_connections(
+            &input_units,
+            &[
+                (InputIndex(0), TrnSysUnit::Kelvin),
+                (InputIndex(1), TrnSysUnit::Watt),
+                (InputIndex(2), TrnSysUnit::Watt),
+            ],
+        );
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].0, InputIndex(1));
+        assert!(matches!(errors[0].1, TrnSysError::IncompatibleUnits { .. }));
+        assert_eq!(errors[1].0, InputIndex(2));
+        assert!(matches!(
+            errors[1].1,
+            TrnSysError::UndeclaredUnit { index: 2, .. }
+        ));
+    }
+}
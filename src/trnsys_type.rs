@@ -1,6 +1,7 @@
+use crate::trnsys::dimensions::{Dimensions, Layout};
 use crate::trnsys::error::{InputError, TrnSysError};
-use crate::trnsys::iteration_mode::IterationMode;
-use crate::trnsys::param::TrnSysValue;
+use crate::trnsys::param::{ParameterDiff, TrnSysValue};
+use crate::trnsys::unit_catalog::TrnSysUnit;
 use crate::trnsys::*;
 use tracing::{info, warn};
 
@@ -11,34 +12,54 @@ impl TrnSysType {
         TrnSysType {}
     }
 
-    /// The very first call of the simulation.
-    /// At this time, **only the number of parameters, inputs, or outputs are available**.
-    /// You can change the number of parameters, inputs, or outputs at this time.
-    /// If inconsistent, error will be automatically raised.
-    pub fn first_call_of_simulation(&self, state: &mut TrnSysState) -> Result<(), TrnSysError> {
+    /// The sizing phase, at the very first call of the simulation.
+    /// Declares the number of parameters, inputs, outputs, and derivatives, as fixed counts or
+    /// cycles repeated as many times as a parameter says, e.g. for a tank with N nodes:
+    /// `inputs: Layout::fixed(2).cycle("nodes", 2, ParamIndex(0))`.
+    /// The counts are computed from the parameters in the deck, and an error explaining the
+    /// computation is raised if the deck gives other counts.
+    pub fn dimensions(&self) -> Dimensions {
+        // TODO: Declare the parameters, inputs, outputs, and derivatives
+        Dimensions {
+            params: Layout::fixed(1),
+            inputs: Layout::fixed(1),
+            outputs: Layout::fixed(1),
+            derivatives: Layout::fixed(0),
+        }
+    }
+
+    /// The very first call of the simulation, after the sizing phase.
+    /// At this time, **only the counts and the parameters are available**, the cycles computed
+    /// by the sizing phase are in `state.sizes`.
+    pub fn first_call_of_simulation(
+        &self,
+        _ctx: &CallContext,
+        state: &mut TrnSysState,
+    ) -> Result<(), TrnSysError> {
         // All the "Very First Call of the Simulation Manipulations"
-        // TODO: Set the number of parameters, inputs, outputs, and derivatives
-        state.num_inputs = 1;
-        state.num_params = 1;
-        state.num_derivatives = 0;
-        state.num_outputs = 1;
+        state.input_units = vec![TrnSysUnit::Dimensionless];
+        state.output_units = vec![TrnSysUnit::Dimensionless];
         Ok(())
     }
 
     /// Validate the input parameters.
     /// If not valid, raise `InputError::BadInput` or `InputError::BadParameter` to stop the simulation.
-    pub fn validate_parameters(&self, state: &mut TrnSysState) -> Result<(), InputError> {
+    pub fn validate_parameters(
+        &self,
+        _ctx: &CallContext,
+        state: &mut TrnSysState,
+    ) -> Result<(), InputError> {
         // Validate the parameters
         let param0: i32 = state
             .params
-            .get(0)
+            .first()
             .ok_or(InputError::BadParameter {
-                index: 0,
+                index: ParamIndex(0),
                 message: "Parameter 1 is missing".to_string(),
             })?
             .try_into()
             .map_err(|e| InputError::BadParameter {
-                index: 0,
+                index: ParamIndex(0),
                 message: format!("{:?}", e),
             })?;
 
@@ -46,23 +67,51 @@ impl TrnSysType {
         if param0 <= 0 {
             warn!("Parameter 0 is less than or equal to 0");
             return Err(InputError::BadParameter {
-                index: 0,
+                index: ParamIndex(0),
                 message: "Parameter 1 must be greater than 0".to_string(),
             });
         }
 
         Ok(())
     }
+    /// Called when the kernel asked to reread the parameters and some of them changed, after they
+    /// passed `validate_parameters` again and before the next `iterate`.
+    /// Rebuild the data derived from the parameters here, e.g. lookup tables or matrices.
+    pub fn on_parameters_changed(
+        &self,
+        _ctx: &CallContext,
+        _state: &mut TrnSysState,
+        diff: &ParameterDiff,
+    ) -> Result<(), TrnSysError> {
+        for &i in &diff.changed {
+            info!(
+                "{} changed from {:?} to {:?}",
+                i,
+                diff.old.get(i.0).map(|value| value.value),
+                diff.new.get(i.0).map(|value| value.value)
+            );
+        }
+        Ok(())
+    }
+
     /// This function is called at the beginning of each simulation.
     /// Do start calculations here and store the results in the static store
-    pub fn simulation_starts(&self, state: &mut TrnSysState) -> Result<(), TrnSysError> {
+    pub fn simulation_starts(
+        &self,
+        _ctx: &CallContext,
+        _state: &mut TrnSysState,
+    ) -> Result<(), TrnSysError> {
         info!("Simulation Starts");
         Ok(())
     }
 
     /// Whether the simulation ends correctly or ends in error, each Type is recalled by the TRNSYS
     /// kernel before the simulation shuts down.
-    pub fn simulation_ends(&self, state: &mut TrnSysState) -> Result<(), TrnSysError> {
+    pub fn simulation_ends(
+        &self,
+        _ctx: &CallContext,
+        _state: &mut TrnSysState,
+    ) -> Result<(), TrnSysError> {
         // Do All of the Last Call Manipulations Here
         info!("Simulation Ends");
         Ok(())
@@ -70,35 +119,101 @@ impl TrnSysType {
 
     /// The TRNSYS kernel calls this function at each time step. \
     /// This function will be called one or more times at each time step. \
-    /// This function should return the values of the outputs for the current time step. \
+    /// This function should write the values of the outputs for the current time step into `outputs`,
+    /// which holds the outputs of the previous iteration. \
+    /// `outputs` is moved out of `state` for the call, so `state.outputs` is empty here. \
     /// TrnSys will take care of the convergence of the simulation.
-    pub fn iterate(&self, state: &mut TrnSysState) -> Result<Vec<TrnSysValue>, TrnSysError> {
-        let time = get_simulation_time();
-        let timestep = get_simulation_time_step();
-        let current_unit = get_current_unit();
-        let current_type = get_current_type();
-        let first: f64 = state.inputs.get(0).unwrap().into();
-        let param0: i32 = state.params.get(0).unwrap().try_into()?;
-
-        Ok(vec![(first * param0 as f64).into()])
+    pub fn iterate(
+        &self,
+        _ctx: &CallContext,
+        state: &mut TrnSysState,
+        outputs: &mut [TrnSysValue],
+    ) -> Result<(), TrnSysError> {
+        // the time, time step, unit and type of the call are in `ctx`
+        let first: f64 = (&state.inputs[0]).into();
+        let param0: i32 = (&state.params[0]).try_into()?;
+
+        outputs[0] = (first * param0 as f64).into();
+        Ok(())
     }
 
     /// At the end of each time step, each Type in a simulation is recalled.
     /// If necessary, store the values of the outputs for the current time step
     /// in the dynamic storage
-    pub fn end_of_timestep(&self, state: &mut TrnSysState) -> Result<(), TrnSysError> {
+    pub fn end_of_timestep(
+        &self,
+        _ctx: &CallContext,
+        _state: &mut TrnSysState,
+    ) -> Result<(), TrnSysError> {
         // Perform Any "End of Timestep" Manipulations That May Be Required
         Ok(())
     }
 
     pub fn get_default_output_values(
         &self,
+        _ctx: &CallContext,
         state: &mut TrnSysState,
     ) -> Result<Vec<TrnSysValue>, TrnSysError> {
         // initialize output values
         let default_outputs = (1..(state.num_outputs + 1))
-            .map(|i| TrnSysValue { value: 0. })
+            .map(|_| TrnSysValue { value: 0. })
             .collect();
         Ok(default_outputs)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trnsys::unit_catalog::check_connections;
+
+    /// The units of the outputs connected to the inputs of the Type in the decks it is used in,
+    /// e.g. from the proformas of the upstream Types: `(InputIndex(0), TrnSysUnit::Celsius)`.
+    const CONNECTED_OUTPUTS: &[(InputIndex, TrnSysUnit)] =
+        &[(InputIndex(0), TrnSysUnit::Dimensionless)];
+
+    /// The units the Type declares for its inputs at the first call.
+    fn input_units() -> Vec<TrnSysUnit> {
+        let type_instance = TrnSysType::new();
+        let mut state = TrnSysState::new();
+        let ctx = CallContext::new(
+            CallPhase::FirstCall,
+            0.,
+            1.,
+            1,
+            1,
+            crate::TYPE_NUMBER as i32,
+        );
+        type_instance
+            .first_call_of_simulation(&ctx, &mut state)
+            .unwrap();
+        state.input_units
+    }
+
+    /// Checks the connections offline, against the units declared at the first call.
+    #[test]
+    fn connected_outputs_match_the_input_units() {
+        let errors = check_connections(&input_units(), CONNECTED_OUTPUTS);
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn mismatched_connections_are_reported() {
+        let connected_outputs = [
+            (InputIndex(0), TrnSysUnit::Dimensionless),
+            (InputIndex(0), TrnSysUnit::Celsius),
+        ];
+        let errors = check_connections(&input_units(), &connected_outputs);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(matches!(
+            errors[0],
+            (
+                InputIndex(0),
+                TrnSysError::IncompatibleUnits {
+                    output: TrnSysUnit::Celsius,
+                    input: TrnSysUnit::Dimensionless
+                }
+            )
+        ));
+    }
+}